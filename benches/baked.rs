@@ -0,0 +1,38 @@
+//! Times sampling a five deep stack of combinators live against the same stack baked into a table.
+//!
+//! Run with `cargo bench --bench baked`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use buttplug_patterns::{shapes::SineWave, transformers::EaseKind, Pattern, PatternGenerator};
+
+const TICKRATE_HZ: f64 = 1000.0;
+const SAMPLES: u32 = 1_000_000;
+
+fn stack() -> impl Pattern {
+    SineWave::new(1.0, Duration::from_secs(2))
+        .scale_time(1.5)
+        .moving_average(Duration::from_millis(100), 8)
+        .ease(EaseKind::SmoothStep)
+        .accel_limit(20.0)
+        .clamp(0.1, 0.9)
+        .forever()
+}
+
+fn time_samples(name: &str, pattern: &mut dyn PatternGenerator) {
+    let start = Instant::now();
+    for tick in 0..SAMPLES {
+        black_box(pattern.sample(Duration::from_secs_f64(tick as f64 / TICKRATE_HZ)));
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{name}: {:?} per sample over {SAMPLES} samples",
+        elapsed / SAMPLES
+    );
+}
+
+fn main() {
+    time_samples("live", &mut stack());
+    time_samples("baked", &mut stack().bake(TICKRATE_HZ).unwrap());
+}
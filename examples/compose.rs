@@ -0,0 +1,18 @@
+//! Builds a pattern from shapes and transformers and prints it, without needing a device.
+
+use std::time::Duration;
+
+use buttplug_patterns::{
+    shapes::{Pause, SineWave},
+    Pattern, PatternGenerator,
+};
+
+fn main() {
+    let mut pattern = SineWave::new(1.0, Duration::from_secs_f64(1.0))
+        .repeat(2.0)
+        .chain(Pause::new(Duration::from_secs_f64(1.0)))
+        .scale_intensity(0.8);
+    for (tick, value) in pattern.render(10.0).iter().enumerate() {
+        println!("{:.1}s {value:.3}", tick as f64 / 10.0);
+    }
+}
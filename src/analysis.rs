@@ -0,0 +1,271 @@
+use std::{error::Error, f64::consts::TAU, fmt, time::Duration};
+
+use crate::{finite_length, PatternGenerator};
+
+/// Problems found in a pattern by `Pattern::validate`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PatternWarnings {
+    /// Whether the pattern has a zero duration, so it would end as soon as it starts.
+    pub zero_duration: bool,
+    /// Whether the pattern or any pattern inside of it has a zero duration or period, such as a wave
+    /// with a zero wavelength, which leads to divisions or modulos by zero.
+    pub zero_length: bool,
+    /// Times at which the pattern produced NaN or infinite values.
+    ///
+    /// These usually come from zero wavelengths or durations somewhere in the pattern,
+    /// which lead to divisions or modulos by zero.
+    pub non_finite: Vec<Duration>,
+    /// Times at which the pattern produced values outside of the valid range 0.0 to 1.0.
+    pub out_of_range: Vec<Duration>,
+    /// Whether the resolution to sample at wasn't a positive, finite number, so the pattern wasn't sampled.
+    pub invalid_resolution: bool,
+}
+
+impl PatternWarnings {
+    /// Returns true if no problems were found.
+    pub fn is_empty(&self) -> bool {
+        !self.zero_duration
+            && !self.zero_length
+            && self.non_finite.is_empty()
+            && self.out_of_range.is_empty()
+            && !self.invalid_resolution
+    }
+
+    pub(crate) fn check<P: PatternGenerator + ?Sized>(pattern: &mut P, resolution_hz: f64) -> Self {
+        let mut warnings = PatternWarnings {
+            zero_duration: pattern.duration().is_zero(),
+            zero_length: has_zero_length(pattern),
+            invalid_resolution: !resolution_hz.is_finite() || resolution_hz <= 0.0,
+            ..Default::default()
+        };
+        if warnings.invalid_resolution {
+            return warnings;
+        }
+        let length = finite_length(pattern).unwrap_or(Duration::from_secs(1));
+        // always take at least one sample, so patterns of zero length still report what they produce
+        let count = ((length.as_secs_f64() * resolution_hz).round() as usize).max(1);
+        for tick in 0..count {
+            let time = Duration::from_secs_f64(tick as f64 / resolution_hz);
+            let value = pattern.sample(time);
+            if !value.is_finite() {
+                warnings.non_finite.push(time);
+            } else if !(0.0..=1.0).contains(&value) {
+                warnings.out_of_range.push(time);
+            }
+        }
+        warnings
+    }
+}
+
+/// Whether `pattern` or any of its subpatterns has a zero duration or period.
+fn has_zero_length<P: PatternGenerator + ?Sized>(pattern: &P) -> bool {
+    pattern.duration().is_zero()
+        || pattern.period().is_zero()
+        || pattern.subpatterns().into_iter().any(has_zero_length)
+}
+
+impl fmt::Display for PatternWarnings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut problems = Vec::new();
+        if self.zero_duration {
+            problems.push("pattern has a zero duration".to_string());
+        }
+        if self.zero_length {
+            problems.push("pattern contains a zero duration or period".to_string());
+        }
+        if self.invalid_resolution {
+            problems.push("resolution must be a positive number".to_string());
+        }
+        if let Some(time) = self.non_finite.first() {
+            problems.push(format!(
+                "{} non-finite samples, first at {:?}",
+                self.non_finite.len(),
+                time
+            ));
+        }
+        if let Some(time) = self.out_of_range.first() {
+            problems.push(format!(
+                "{} out of range samples, first at {:?}",
+                self.out_of_range.len(),
+                time
+            ));
+        }
+        write!(f, "{}", problems.join(", "))
+    }
+}
+
+impl Error for PatternWarnings {}
+
+/// Checks whether two patterns are approximately equal, by sampling both at `resolution_hz` samples
+/// per second over the shorter of their durations and comparing each pair of samples.
+///
+/// Returns true if every pair of samples is within `epsilon` of each other.
+/// If both patterns loop forever, they are compared over the shorter of their periods, and if
+/// neither has a finite period there is nothing to compare, so they are never considered equal.
+/// Neither are they if `resolution_hz` isn't a positive, finite number.
+pub fn patterns_approx_equal<A, B>(a: &mut A, b: &mut B, resolution_hz: f64, epsilon: f64) -> bool
+where
+    A: PatternGenerator + ?Sized,
+    B: PatternGenerator + ?Sized,
+{
+    if !resolution_hz.is_finite() || resolution_hz <= 0.0 {
+        return false;
+    }
+    let mut length = a.duration().min(b.duration());
+    if length == Duration::MAX {
+        length = a.period().min(b.period());
+    }
+    if length == Duration::MAX {
+        return false;
+    }
+    let count = (length.as_secs_f64() * resolution_hz).round() as usize;
+    (0..count).all(|tick| {
+        let time = Duration::from_secs_f64(tick as f64 / resolution_hz);
+        (a.sample(time) - b.sample(time)).abs() <= epsilon
+    })
+}
+
+/// Computes the magnitude spectrum of a single period of a pattern, sampled at `resolution_hz`.
+///
+/// Bin `k` is the amplitude of the component that cycles `k` times per period, so bin 0 is the average
+/// intensity and bin 1 is the fundamental. Amplitudes are peak values, so a `SineWave` with an amplitude
+/// of 1.0 has 0.5 in both bin 0 and bin 1. Returns `bins` bins, uses a direct DFT so keep `bins` modest
+/// at high resolutions, and bins above half the number of samples are 0.0. Patterns without a finite
+/// period have no spectrum, so every bin is 0.0, as it is if `resolution_hz` isn't a positive, finite number.
+pub fn spectrum<P: PatternGenerator + ?Sized>(
+    pattern: &mut P,
+    resolution_hz: f64,
+    bins: usize,
+) -> Vec<f64> {
+    let count = match pattern.period() {
+        _ if !resolution_hz.is_finite() || resolution_hz <= 0.0 => 0,
+        Duration::MAX => 0,
+        period => (period.as_secs_f64() * resolution_hz).round() as usize,
+    };
+    let samples: Vec<f64> = (0..count)
+        .map(|tick| pattern.sample(Duration::from_secs_f64(tick as f64 / resolution_hz)))
+        .collect();
+    (0..bins)
+        .map(|k| {
+            if count == 0 || k > count / 2 {
+                return 0.0;
+            }
+            let (re, im) = samples
+                .iter()
+                .enumerate()
+                .fold((0.0, 0.0), |(re, im), (n, value)| {
+                    let angle = TAU * (k * n) as f64 / count as f64;
+                    (re + value * angle.cos(), im - value * angle.sin())
+                });
+            let magnitude = (re * re + im * im).sqrt() / count as f64;
+            // every bin but the average gets half its amplitude from the mirrored negative frequency
+            if k == 0 || 2 * k == count {
+                magnitude
+            } else {
+                2.0 * magnitude
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{patterns_approx_equal, spectrum};
+    use crate::shapes::{Constant, FourierSynth, SineWave};
+    use crate::Pattern;
+
+    #[test]
+    fn validate_reports_zero_wavelength_inside_a_tree() {
+        // the wave is never reached when sampling, so only walking the tree finds it
+        let pattern = Constant::new(0.5, Duration::from_secs(1))
+            .chain(SineWave::new(1.0, Duration::ZERO))
+            .scale_intensity(0.5);
+        let warnings = pattern.validate(10.0).unwrap_err();
+        assert!(warnings.zero_length);
+        assert!(warnings.non_finite.is_empty());
+    }
+
+    #[test]
+    fn validate_samples_zero_wavelength_waves() {
+        let warnings = SineWave::new(1.0, Duration::ZERO)
+            .validate(10.0)
+            .unwrap_err();
+        assert!(warnings.zero_duration);
+        assert_eq!(warnings.non_finite, vec![Duration::ZERO]);
+    }
+
+    #[test]
+    fn validate_accepts_unbounded_patterns() {
+        let pattern = Constant::new(0.5, Duration::MAX);
+        assert_eq!(pattern.validate(10.0), Ok(()));
+    }
+
+    #[test]
+    fn validate_reports_out_of_range_values() {
+        let pattern = Constant::new(0.75, Duration::from_secs(1)).offset(0.5);
+        let warnings = pattern.validate(10.0).unwrap_err();
+        assert!(!warnings.zero_length);
+        assert_eq!(warnings.out_of_range.len(), 10);
+    }
+
+    #[test]
+    fn approx_equal_matches_clones_but_not_scaled_copies() {
+        let mut sine = SineWave::new(0.8, Duration::from_secs(1)).forever();
+        let mut clone = sine.clone();
+        let mut scaled = sine.clone().scale_intensity(0.5);
+        assert!(patterns_approx_equal(&mut sine, &mut clone, 50.0, 1e-9));
+        assert!(!patterns_approx_equal(&mut sine, &mut scaled, 50.0, 1e-3));
+        // compared over the shorter duration
+        let mut short = Constant::new(0.5, Duration::from_secs(1));
+        let mut long = Constant::new(0.5, Duration::from_secs(1))
+            .chain(Constant::new(1.0, Duration::from_secs(1)));
+        assert!(patterns_approx_equal(&mut short, &mut long, 50.0, 1e-9));
+    }
+
+    #[test]
+    fn approx_equal_is_false_without_a_finite_length() {
+        let mut a = Constant::new(0.5, Duration::MAX);
+        let mut b = Constant::new(0.5, Duration::MAX);
+        assert!(!patterns_approx_equal(&mut a, &mut b, 50.0, 1e-9));
+    }
+
+    #[test]
+    fn invalid_resolutions_are_rejected() {
+        let mut sine = SineWave::new(1.0, Duration::from_secs(1));
+        let mut clone = sine;
+        for resolution in [0.0, -10.0, f64::NAN, f64::INFINITY] {
+            let warnings = sine.validate(resolution).unwrap_err();
+            assert!(warnings.invalid_resolution);
+            assert!(warnings.non_finite.is_empty() && warnings.out_of_range.is_empty());
+            assert!(!patterns_approx_equal(
+                &mut sine, &mut clone, resolution, 1e-9
+            ));
+            assert_eq!(spectrum(&mut sine, resolution, 4), vec![0.0; 4]);
+        }
+    }
+
+    #[test]
+    fn spectrum_of_a_pure_sine_has_one_dominant_bin() {
+        let mut sine = SineWave::new(1.0, Duration::from_secs(1));
+        let bins = spectrum(&mut sine, 64.0, 8);
+        assert!((bins[0] - 0.5).abs() < 1e-9 && (bins[1] - 0.5).abs() < 1e-9);
+        assert!(bins[2..].iter().all(|bin| bin.abs() < 1e-9), "{bins:?}");
+
+        let mut harmonic = FourierSynth::new(
+            Duration::from_secs(1),
+            vec![(0.0, 0.0), (0.0, 0.0), (0.3, 0.0)],
+        );
+        let bins = spectrum(&mut harmonic, 64.0, 8);
+        let loudest = (0..8).max_by(|a, b| bins[*a].total_cmp(&bins[*b])).unwrap();
+        assert_eq!(loudest, 3);
+        assert!((bins[3] - 0.3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn spectrum_without_a_finite_period_is_silent() {
+        let mut pattern = Constant::new(0.5, Duration::MAX);
+        assert_eq!(spectrum(&mut pattern, 64.0, 4), [0.0; 4]);
+    }
+}
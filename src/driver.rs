@@ -1,37 +1,402 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
+    error::Error,
+    fmt, io,
+    path::PathBuf,
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         Arc,
     },
-    time::{Duration, Instant},
+    time::Duration,
 };
 
-use buttplug::client::{ButtplugClient, ButtplugClientError, ScalarValueCommand};
-use tokio::time::interval;
-use crate::{PatternGenerator, Pattern};
+use crate::shapes::SquareWave;
+use crate::{finite_length, funscript, Pattern, PatternGenerator};
+use buttplug::client::{
+    ButtplugClient, ButtplugClientDevice, ButtplugClientError, LinearCommand, RotateCommand,
+    ScalarValueCommand,
+};
+use tokio::time::{interval, Instant, Interval};
+
+/// Errors that can occur while running a `Driver`.
+#[derive(Debug)]
+pub enum DriverError {
+    /// A command could not be sent to the buttplug server or a device.
+    Buttplug(ButtplugClientError),
+    /// A recording could not be written to disk.
+    Io(io::Error),
+}
+
+impl fmt::Display for DriverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DriverError::Buttplug(e) => write!(f, "buttplug error: {e}"),
+            DriverError::Io(e) => write!(f, "io error: {e}"),
+        }
+    }
+}
+
+impl Error for DriverError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DriverError::Buttplug(e) => Some(e),
+            DriverError::Io(e) => Some(e),
+        }
+    }
+}
+
+/// An invalid setting given to a `DriverBuilder`.
+#[derive(Debug)]
+pub struct BuildError {
+    pub message: String,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid driver setting: {}", self.message)
+    }
+}
+
+impl Error for BuildError {}
+
+impl From<ButtplugClientError> for DriverError {
+    fn from(e: ButtplugClientError) -> Self {
+        DriverError::Buttplug(e)
+    }
+}
+
+impl From<io::Error> for DriverError {
+    fn from(e: io::Error) -> Self {
+        DriverError::Io(e)
+    }
+}
+
+/// Adds an action to `recording` for a level sent at `time`, if the run is being recorded and anything was sent.
+fn record(recording: &mut Option<Vec<(Duration, f64)>>, time: Duration, sent: Option<f64>) {
+    if let (Some(recording), Some(level)) = (recording, sent) {
+        recording.push((time, level));
+    }
+}
+
+/// Which kind of actuator a `Driver` sends the sampled intensity to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Actuator {
+    /// Vibration motors, driven at the sampled intensity. This is the default.
+    #[default]
+    Vibrate,
+    /// Rotation motors, driven at the sampled intensity as their speed, always turning in the same direction.
+    Rotate { clockwise: bool },
+    /// Linear actuators such as strokers, moved to the sampled intensity as their position,
+    /// taking one tick to get there.
+    Linear,
+}
+
+/// What a `Driver` does when a command to a device fails, such as when a Bluetooth toy disconnects mid run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// End the run and return the error. This is the default.
+    #[default]
+    Abort,
+    /// Skip the failed device for that tick and keep running, trying it again on the next tick.
+    Continue,
+}
+
+/// What a `Driver` does with levels outside of the range 0.0 to 1.0 accepted by devices, such as from
+/// summed patterns or NaN from a zero wavelength.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum OutOfRangePolicy {
+    /// Clamp the level into the range, and send 0.0 for NaN. This is the default.
+    #[default]
+    Clamp,
+    /// Send 0.0 for any level outside of the range, so a misbehaving pattern turns the devices off.
+    Silence,
+}
+
+impl OutOfRangePolicy {
+    fn apply(self, level: f64) -> f64 {
+        match self {
+            _ if level.is_nan() => 0.0,
+            OutOfRangePolicy::Clamp => level.clamp(0.0, 1.0),
+            OutOfRangePolicy::Silence if (0.0..=1.0).contains(&level) => level,
+            OutOfRangePolicy::Silence => 0.0,
+        }
+    }
+}
+
+/// How the driver spreads intensity across devices.
+enum Mode {
+    /// Every device plays its patterns directly.
+    Direct,
+    /// Intensity is panned across devices in index order by a pattern from 0.0 to 1.0.
+    Pan(Box<dyn PatternGenerator>),
+    /// Each device plays the patterns delayed by its position in index order times a step,
+    /// replaying the recent `(time, value)` samples of the global pattern.
+    Wave {
+        step: Duration,
+        history: VecDeque<(Duration, f64)>,
+    },
+    /// Each device's intensity is scaled by its weight, keyed by device index.
+    Weighted(HashMap<u32, f64>),
+}
+
+/// Delay of the device at `position` in a wave moving `step` per device, saturating at `Duration::MAX`
+/// so devices too far along the wave stay silent.
+fn wave_delay(step: Duration, position: usize) -> Duration {
+    u32::try_from(position)
+        .ok()
+        .and_then(|position| step.checked_mul(position))
+        .unwrap_or(Duration::MAX)
+}
+
+/// The latest sample in a wave's `history` taken at or before `time`, or 0.0 if there is none.
+fn wave_sample(history: &VecDeque<(Duration, f64)>, time: Duration) -> f64 {
+    let next = history.partition_point(|(at, _)| *at <= time);
+    next.checked_sub(1).map_or(0.0, |index| history[index].1)
+}
+
+/// Triangular weight of the device at `position` out of `count` devices, for a pan position from 0.0 to 1.0.
+fn pan_weight(pan: f64, position: usize, count: usize) -> f64 {
+    if count <= 1 {
+        return 1.0;
+    }
+    let center = pan.clamp(0.0, 1.0) * (count - 1) as f64;
+    (1.0 - (center - position as f64).abs()).max(0.0)
+}
+
+/// Weights of devices with the given battery levels, proportional to their charge and averaging to 1.0,
+/// so the total intensity across all devices is preserved.
+///
+/// Devices without a battery level are weighted as if they had the average charge of the others.
+fn battery_weights(levels: &[Option<f64>]) -> Vec<f64> {
+    let known: Vec<f64> = levels.iter().flatten().copied().collect();
+    let average = if known.is_empty() {
+        1.0
+    } else {
+        known.iter().sum::<f64>() / known.len() as f64
+    };
+    let levels: Vec<f64> = levels
+        .iter()
+        .map(|level| level.unwrap_or(average))
+        .collect();
+    let total: f64 = levels.iter().sum();
+    if total <= 0.0 {
+        return vec![1.0; levels.len()];
+    }
+    levels
+        .iter()
+        .map(|level| level * levels.len() as f64 / total)
+        .collect()
+}
+
+/// Weight of the group pattern when `count` devices are connected, rising linearly from 0.0 with a single
+/// device to 1.0 with `full_at` devices or more.
+fn crowd_weight(count: usize, full_at: usize) -> f64 {
+    if full_at <= 1 {
+        return 1.0;
+    }
+    (count.saturating_sub(1) as f64 / (full_at - 1) as f64).min(1.0)
+}
+
+/// Blends between a solo and a group pattern depending on how many devices are currently connected.
+struct CrowdBlend {
+    buttplug: Arc<ButtplugClient>,
+    solo: Box<dyn PatternGenerator>,
+    group: Box<dyn PatternGenerator>,
+    full_at: usize,
+}
+
+impl PatternGenerator for CrowdBlend {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let weight = crowd_weight(self.buttplug.devices().len(), self.full_at);
+        let solo = self.solo.sample(time);
+        let group = self.group.sample(time);
+        solo * (1.0 - weight) + group * weight
+    }
+
+    fn duration(&self) -> Duration {
+        self.solo.duration().max(self.group.duration())
+    }
+
+    fn period(&self) -> Duration {
+        self.solo.period().max(self.group.period())
+    }
+
+    fn reset(&mut self) {
+        self.solo.reset();
+        self.group.reset();
+    }
+}
+
+/// How long the sustained intensity limit takes to ramp down to its safe level once triggered.
+const SUSTAINED_RAMP_DOWN: Duration = Duration::from_secs(1);
+
+/// Caps the intensity once the global intensity has stayed above a threshold for too long.
+struct SustainedLimit {
+    threshold: f64,
+    max_on: Duration,
+    safe_level: f64,
+    high_since: Option<Instant>,
+    limited_since: Option<Instant>,
+}
+
+impl SustainedLimit {
+    /// Updates the limit with the global intensity at `now`, and returns the highest level allowed then.
+    fn ceiling(&mut self, intensity: f64, now: Instant) -> f64 {
+        if intensity <= self.threshold {
+            self.high_since = None;
+            self.limited_since = None;
+            return f64::INFINITY;
+        }
+        let high_since = *self.high_since.get_or_insert(now);
+        if now - high_since < self.max_on {
+            return f64::INFINITY;
+        }
+        let limited_since = *self.limited_since.get_or_insert(now);
+        let progress =
+            ((now - limited_since).as_secs_f64() / SUSTAINED_RAMP_DOWN.as_secs_f64()).min(1.0);
+        1.0 + (self.safe_level - 1.0) * progress
+    }
+
+    fn reset(&mut self) {
+        self.high_since = None;
+        self.limited_since = None;
+    }
+}
+
+/// Time between ticks of a driver running at `tickrate_hz`.
+///
+/// Ticks are whole milliseconds, a tickrate of 0 is treated as 1 Hz and tickrates above 1000 Hz as 1000 Hz.
+fn tick_period(tickrate_hz: u64) -> Duration {
+    Duration::from_millis((1000 / tickrate_hz.max(1)).max(1))
+}
+
+/// Renders the `(time, level)` scalar commands a `Driver` running at `tickrate_hz` would send
+/// while playing `pattern` once, without needing a device or server.
+///
+/// Commands are spaced by the driver's tick period and clamped the same way the driver clamps them.
+/// Infinite patterns are rendered for a single period, and patterns without a finite period
+/// render no commands.
+pub fn render_commands<P: PatternGenerator + ?Sized>(
+    pattern: &mut P,
+    tickrate_hz: u64,
+) -> Vec<(Duration, f64)> {
+    let Some(length) = finite_length(pattern) else {
+        return Vec::new();
+    };
+    let period = tick_period(tickrate_hz);
+    (0..)
+        .map(|tick| period * tick)
+        .take_while(|time| *time <= length)
+        .map(|time| (time, OutOfRangePolicy::Clamp.apply(pattern.sample(time))))
+        .collect()
+}
+
+/// Shared handle to the tickrate of a `Driver`, for changing it from another task while the driver runs.
+///
+/// Created with `Driver::tickrate_handle`.
+#[derive(Clone, Debug)]
+pub struct TickrateHandle(Arc<AtomicU64>);
+
+impl TickrateHandle {
+    /// Returns the current tickrate, in Hz.
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Acquire)
+    }
+
+    /// Sets the tickrate, in Hz. A running driver switches to it on its next tick.
+    /// A tickrate of 0 is treated as 1 Hz.
+    pub fn set(&self, hz: u64) {
+        self.0.store(hz.max(1), Ordering::Release);
+    }
+}
+
+/// Shared handle for pausing and resuming a `Driver` from another task while it runs.
+///
+/// Created with `Driver::pause_handle`.
+#[derive(Clone, Debug, Default)]
+pub struct PauseHandle(Arc<AtomicBool>);
+
+impl PauseHandle {
+    /// Returns true if the driver is paused.
+    pub fn is_paused(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+
+    /// Pauses the driver, stopping every device and freezing the patterns' clocks until it is resumed.
+    pub fn pause(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    /// Resumes a paused driver, continuing the patterns from where they were paused.
+    pub fn resume(&self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+// 10 hz is fast enough to feel smooth without overwhelming the device or server in my testing
+const DEFAULT_TICKRATE_HZ: u64 = 10;
 
 /// Driver that can send patterns to buttplug devices.
 pub struct Driver {
     pub buttplug: Arc<ButtplugClient>,
-    tickrate_hz: u64,
+    tickrate_hz: TickrateHandle,
+    paused: PauseHandle,
     pattern: Box<dyn PatternGenerator>,
     device_patterns: HashMap<u32, Box<dyn PatternGenerator>>,
     actuator_patterns: HashMap<(u32, u32), Box<dyn PatternGenerator>>,
+    funscript_path: Option<PathBuf>,
+    recording_epsilon: Option<f64>,
+    start_ramp: Duration,
+    stop_ramp: Duration,
+    min_change: Option<f64>,
+    last_sent: HashMap<u32, HashMap<u32, f64>>,
+    sustained_limit: Option<SustainedLimit>,
+    actuator: Actuator,
+    error_policy: ErrorPolicy,
+    max_intensity: f64,
+    device_filter: Option<DeviceFilter>,
+    out_of_range_policy: OutOfRangePolicy,
+    mode: Mode,
 }
 
+/// Predicate picking which devices a `Driver` actuates.
+type DeviceFilter = Box<dyn Fn(&ButtplugClientDevice) -> bool>;
+
 impl Driver {
     /// Creates a new driver with a given ButtplugClient and Pattern.
     ///
     /// The ButtplugClient is passed via an Arc to allow for applications to maintain access to the client
     /// after the driver has been created.
     pub fn new<P: 'static + Pattern>(bp: Arc<ButtplugClient>, pattern: P) -> Self {
+        Self::with_boxed_pattern(bp, Box::new(pattern))
+    }
+
+    /// Starts building a driver with chained configuration, see `DriverBuilder`.
+    pub fn builder() -> DriverBuilder {
+        DriverBuilder::default()
+    }
+
+    fn with_boxed_pattern(bp: Arc<ButtplugClient>, pattern: Box<dyn PatternGenerator>) -> Self {
         Driver {
             buttplug: bp,
-            tickrate_hz: 10, // 10 hz is fast enough to feel smooth without overwhelming the device or server in my testing
-            pattern: Box::new(pattern),
+            tickrate_hz: TickrateHandle(Arc::new(AtomicU64::new(DEFAULT_TICKRATE_HZ))),
+            paused: PauseHandle::default(),
+            pattern,
             device_patterns: HashMap::new(),
             actuator_patterns: HashMap::new(),
+            funscript_path: None,
+            recording_epsilon: None,
+            start_ramp: Duration::ZERO,
+            stop_ramp: Duration::ZERO,
+            min_change: None,
+            last_sent: HashMap::new(),
+            sustained_limit: None,
+            actuator: Actuator::Vibrate,
+            error_policy: ErrorPolicy::Abort,
+            max_intensity: 1.0,
+            device_filter: None,
+            out_of_range_policy: OutOfRangePolicy::Clamp,
+            mode: Mode::Direct,
         }
     }
 
@@ -40,7 +405,90 @@ impl Driver {
     ///
     /// The default tickrate is 10 Hz.
     pub fn set_tickrate(&mut self, hz: u64) -> &mut Self {
-        self.tickrate_hz = hz;
+        self.tickrate_hz.set(hz);
+        self
+    }
+
+    /// Returns the current tickrate of the driver, in Hz.
+    pub fn tickrate(&self) -> u64 {
+        self.tickrate_hz.get()
+    }
+
+    /// Sets the tickrate of the driver, in Hz, taking effect on the next tick of a running driver.
+    ///
+    /// Since the driver is borrowed while it runs, use `tickrate_handle` to change the tickrate
+    /// from another task during a run.
+    pub fn set_tickrate_live(&self, hz: u64) {
+        self.tickrate_hz.set(hz);
+    }
+
+    /// Returns a handle that can change the tickrate of the driver from another task while it runs.
+    pub fn tickrate_handle(&self) -> TickrateHandle {
+        self.tickrate_hz.clone()
+    }
+
+    /// Pauses the driver, stopping every device and freezing the patterns' clocks until it is resumed.
+    ///
+    /// Since the driver is borrowed while it runs, use `pause_handle` to pause from another task during a run.
+    /// Pausing before a run starts holds the run paused until it is resumed.
+    pub fn pause(&self) {
+        self.paused.pause();
+    }
+
+    /// Resumes a paused driver, continuing the patterns from where they were paused.
+    pub fn resume(&self) {
+        self.paused.resume();
+    }
+
+    /// Returns a handle that can pause and resume the driver from another task while it runs.
+    pub fn pause_handle(&self) -> PauseHandle {
+        self.paused.clone()
+    }
+
+    /// Sets which kind of actuator the driver sends intensities to. By default the driver vibrates devices.
+    ///
+    /// Devices without any actuator of that kind are skipped. Patterns set for specific actuators with
+    /// `set_actuator_pattern` are looked up by the actuator index for vibration, and by the position of
+    /// the feature in the device's rotation or linear features for rotation and linear movement.
+    pub fn set_actuator(&mut self, actuator: Actuator) -> &mut Self {
+        self.actuator = actuator;
+        self
+    }
+
+    /// Sets what the driver does when a command to a device fails. By default the run ends with the error.
+    ///
+    /// Errors stopping the devices at the end of a run are always returned.
+    pub fn set_error_policy(&mut self, policy: ErrorPolicy) -> &mut Self {
+        self.error_policy = policy;
+        self
+    }
+
+    /// Scales every level sent to the devices so a full intensity of 1.0 is sent as `max`, as an overall
+    /// volume control. The default is 1.0.
+    pub fn set_max_intensity(&mut self, max: f64) -> &mut Self {
+        self.max_intensity = max;
+        self
+    }
+
+    /// Only actuates the devices for which `filter` returns true. Other devices are left alone and don't count
+    /// towards the positions used by panning and waves. By default every device is actuated.
+    pub fn set_device_filter<F: 'static + Fn(&ButtplugClientDevice) -> bool>(
+        &mut self,
+        filter: F,
+    ) -> &mut Self {
+        self.device_filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Actuates every device again, removing the filter set by `set_device_filter`.
+    pub fn clear_device_filter(&mut self) -> &mut Self {
+        self.device_filter = None;
+        self
+    }
+
+    /// Sets what the driver does with levels outside of the range 0.0 to 1.0. By default they are clamped.
+    pub fn set_out_of_range_policy(&mut self, policy: OutOfRangePolicy) -> &mut Self {
+        self.out_of_range_policy = policy;
         self
     }
 
@@ -51,6 +499,26 @@ impl Driver {
         self
     }
 
+    /// Sets the global pattern of the driver to a blend of `solo` and `group` that follows how many devices
+    /// are connected, so devices coming and going during a run change the feel.
+    ///
+    /// With a single device only `solo` plays, with `full_at` devices or more only `group` plays,
+    /// and counts in between crossfade linearly. The blend lasts as long as the longer of the two patterns.
+    pub fn set_crowd_pattern<S: 'static + PatternGenerator, G: 'static + PatternGenerator>(
+        &mut self,
+        solo: S,
+        group: G,
+        full_at: usize,
+    ) -> &mut Self {
+        self.pattern = Box::new(CrowdBlend {
+            buttplug: self.buttplug.clone(),
+            solo: Box::new(solo),
+            group: Box::new(group),
+            full_at,
+        });
+        self
+    }
+
     /// Sets the pattern of a specific device based on its index.
     ///
     /// Device indexes can be found using the `index()` method of the `ButtplugClientDevice`.
@@ -87,49 +555,1495 @@ impl Driver {
         self
     }
 
+    /// Sets how long the driver ramps up from 0.0 to the initial values of the patterns when a run starts.
+    ///
+    /// The patterns' clocks start once the ramp has finished, so no part of the patterns is skipped,
+    /// and stateful patterns are reset so sampling their start during the ramp doesn't advance them.
+    /// The default is no ramp.
+    pub fn set_start_ramp(&mut self, ramp: Duration) -> &mut Self {
+        self.start_ramp = ramp;
+        self
+    }
+
+    /// Sets how long the driver ramps down from the last values of the patterns to 0.0 when a run ends,
+    /// either because it was cancelled or because the pattern finished.
+    ///
+    /// The patterns are held where they stopped while they fade out. Runs that end because of an error
+    /// skip the ramp. Devices are always stopped once a run has ended. The default is no ramp.
+    pub fn set_stop_ramp(&mut self, ramp: Duration) -> &mut Self {
+        self.stop_ramp = ramp;
+        self
+    }
+
+    /// Skips sending a command to a device when none of its actuator levels have changed by more than
+    /// `epsilon` since the last command sent to it.
+    ///
+    /// This reduces Bluetooth traffic and battery drain during constant sections of a pattern.
+    /// Devices are still stopped when a run ends. By default every command is sent.
+    pub fn set_min_change(&mut self, epsilon: f64) -> &mut Self {
+        self.min_change = Some(epsilon);
+        self
+    }
+
+    /// Sends every command, even if it hasn't changed since the last one.
+    pub fn remove_min_change(&mut self) -> &mut Self {
+        self.min_change = None;
+        self
+    }
+
+    /// Turns every device down to `safe_level` once the global intensity has stayed above `threshold`
+    /// for longer than `max_on`, as a comfort limit for long sessions.
+    ///
+    /// The limit ramps down over one second once triggered, and is lifted as soon as the global intensity
+    /// drops to `threshold` or below. By default there is no limit.
+    pub fn set_sustained_limit(
+        &mut self,
+        threshold: f64,
+        max_on: Duration,
+        safe_level: f64,
+    ) -> &mut Self {
+        self.sustained_limit = Some(SustainedLimit {
+            threshold,
+            max_on,
+            safe_level,
+            high_since: None,
+            limited_since: None,
+        });
+        self
+    }
+
+    /// Removes the sustained intensity limit.
+    pub fn remove_sustained_limit(&mut self) -> &mut Self {
+        self.sustained_limit = None;
+        self
+    }
+
+    /// Records the levels sent to the devices on every tick of a run to a funscript file at `path`.
+    ///
+    /// Levels are recorded after modes, limits and `set_min_change` are applied, and ticks that send nothing
+    /// are left out. When devices are sent different levels, the highest is recorded.
+    /// The file is written when the run ends, including when it is stopped early through the `running` flag of
+    /// `run_while` or a device command fails. Dropping the run's future instead skips writing the file.
+    pub fn record_to_funscript(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.funscript_path = Some(path.into());
+        self
+    }
+
+    /// Merges recorded actions that are within `epsilon` of each other into a pair of anchor actions
+    /// before the recording is written, shrinking recordings of constant sections.
+    pub fn set_recording_epsilon(&mut self, epsilon: f64) -> &mut Self {
+        self.recording_epsilon = Some(epsilon);
+        self
+    }
+
+    /// Records an action for every tick of a run, without merging them.
+    pub fn remove_recording_epsilon(&mut self) -> &mut Self {
+        self.recording_epsilon = None;
+        self
+    }
+
+    /// Stops recording runs to a funscript file.
+    pub fn stop_recording(&mut self) -> &mut Self {
+        self.funscript_path = None;
+        self
+    }
+
     /// Runs the driver, actuating all connected devices with the current pattern. All devices will stop when `run` exits.
-    pub async fn run(&mut self) -> Result<(), ButtplugClientError> {
-        self.run_while(AtomicBool::new(true)).await
+    pub async fn run(&mut self) -> Result<(), DriverError> {
+        self.run_while(Arc::new(AtomicBool::new(true))).await
     }
 
-    /// Runs the driver, actuating all connected devices with the current pattern, while the `running` is true.
+    /// Runs the driver, actuating all connected devices with the current pattern, while `running` is true.
     ///
-    /// This is useful for when you want to cancel the driver early. All devices will stop when `run_while` exits.
-    pub async fn run_while(&mut self, running: AtomicBool) -> Result<(), ButtplugClientError> {
-        self.pattern.reset();
-        self.device_patterns.values_mut().for_each(|p| p.reset());
-        self.actuator_patterns.values_mut().for_each(|p| p.reset());
+    /// This is useful for when you want to cancel the driver early: clear `running` from another task and
+    /// the run ends on its next tick. All devices will stop and the recording is written when `run_while` exits.
+    pub async fn run_while(&mut self, running: Arc<AtomicBool>) -> Result<(), DriverError> {
+        self.run_session(&running, false).await
+    }
+
+    /// Runs the driver like `run_while`, but loops the global pattern from the start each time its duration ends,
+    /// until `running` is false.
+    ///
+    /// Unlike running a `.forever()` pattern, the pattern keeps its finite duration, so progress through
+    /// each loop can still be queried. Device and actuator patterns loop with the global pattern.
+    pub async fn run_looping(&mut self, running: Arc<AtomicBool>) -> Result<(), DriverError> {
+        self.run_session(&running, true).await
+    }
+
+    async fn run_session(
+        &mut self,
+        running: &AtomicBool,
+        looping: bool,
+    ) -> Result<(), DriverError> {
+        self.reset_patterns();
+        self.last_sent.clear();
+        if let Some(limit) = &mut self.sustained_limit {
+            limit.reset();
+        }
+        let mut recording = self.funscript_path.as_ref().map(|_| Vec::new());
+        let result = self.drive(running, looping, &mut recording).await;
+        // stop the devices even if the run failed, so they aren't left running at their last level
+        let stopped = self.buttplug.stop_all_devices().await;
+        if let (Some(path), Some(mut recording)) = (&self.funscript_path, recording) {
+            if let Some(epsilon) = self.recording_epsilon {
+                recording = funscript::merge_actions(&recording, epsilon);
+            }
+            funscript::save_funscript(path, &recording)?;
+        }
+        result?;
+        Ok(stopped?)
+    }
+
+    /// Runs the driver like `run`, with `intensity` as the global pattern, panned smoothly across all devices
+    /// in index order by `pan`.
+    ///
+    /// A pan of 0.0 puts all of the intensity on the first device and 1.0 puts it all on the last,
+    /// values in between share it between the two nearest devices. Patterns of specific devices and actuators
+    /// are panned the same way. With a single device, the pan has no effect.
+    ///
+    /// The global pattern set before the run is restored once it ends.
+    pub async fn run_pan<I: 'static + Pattern, P: 'static + Pattern>(
+        &mut self,
+        intensity: I,
+        pan: P,
+    ) -> Result<(), DriverError> {
+        let pattern = std::mem::replace(&mut self.pattern, Box::new(intensity));
+        self.mode = Mode::Pan(Box::new(pan));
+        let result = self.run().await;
+        self.mode = Mode::Direct;
+        self.pattern = pattern;
+        result
+    }
+
+    /// Runs the driver like `run`, with `pattern` as the global pattern, bouncing it back and forth between
+    /// two devices, swapping `rate_hz` times per second.
+    ///
+    /// The first device in index order plays the pattern first while the other is silent, then they swap.
+    /// With more than two devices it bounces between the first and the last, and the rest stay silent.
+    /// With a single device it plays the pattern without interruption.
+    ///
+    /// The global pattern set before the run is restored once it ends.
+    pub async fn run_ping_pong<P: 'static + Pattern>(
+        &mut self,
+        pattern: P,
+        rate_hz: f64,
+    ) -> Result<(), DriverError> {
+        let wavelength = Duration::try_from_secs_f64(2.0 / rate_hz).unwrap_or(Duration::MAX);
+        // a square wave panning hard from the first device to the last each half wavelength
+        let pan = SquareWave::new(1.0, wavelength).invert(1.0).forever();
+        self.run_pan(pattern, pan).await
+    }
+
+    /// Runs the driver like `run`, sending the patterns across all devices in index order as a traveling wave.
+    ///
+    /// The device at position `i` plays the patterns `i * phase_step` later than the first device,
+    /// and stays silent until its delay has passed. The run ends when the first device reaches the end
+    /// of the global pattern, so this works best with patterns that loop forever.
+    ///
+    /// The global pattern is sampled once per tick, and later devices replay its earlier samples, so stateful
+    /// patterns play the same on every device. Delays are rounded down to whole ticks of the global pattern.
+    /// Device and actuator patterns are sampled at each device's own delayed time.
+    pub async fn run_wave(&mut self, phase_step: Duration) -> Result<(), DriverError> {
+        self.mode = Mode::Wave {
+            step: phase_step,
+            history: VecDeque::new(),
+        };
+        let result = self.run().await;
+        self.mode = Mode::Direct;
+        result
+    }
+
+    /// Runs the driver like `run`, with `pattern` as the global pattern, sharing the intensity across devices
+    /// in proportion to their battery level so devices with more charge take more of the load.
+    ///
+    /// Battery levels are read once when the run starts. The weights average to 1.0, so the total intensity
+    /// is preserved, though highly charged devices may be clamped at full intensity. Devices that don't report
+    /// a battery level, or connect during the run, get an equal share.
+    ///
+    /// The global pattern set before the run is restored once it ends.
+    pub async fn run_battery_weighted<P: 'static + Pattern>(
+        &mut self,
+        pattern: P,
+    ) -> Result<(), DriverError> {
+        let pattern = std::mem::replace(&mut self.pattern, Box::new(pattern));
+        let devices = self.buttplug.devices();
+        let mut levels = Vec::with_capacity(devices.len());
+        for device in &devices {
+            levels.push(if device.has_battery_level() {
+                device.battery_level().await.ok()
+            } else {
+                None
+            });
+        }
+        let weights = devices
+            .iter()
+            .map(|device| device.index())
+            .zip(battery_weights(&levels))
+            .collect();
+        self.mode = Mode::Weighted(weights);
+        let result = self.run().await;
+        self.mode = Mode::Direct;
+        self.pattern = pattern;
+        result
+    }
+
+    async fn drive(
+        &mut self,
+        running: &AtomicBool,
+        looping: bool,
+        recording: &mut Option<Vec<(Duration, f64)>>,
+    ) -> Result<(), ButtplugClientError> {
+        let run_start = Instant::now();
+        let mut tickrate_hz = self.tickrate_hz.get();
+        let mut interval = interval(tick_period(tickrate_hz));
+        let mut paused_total = Duration::ZERO;
+        let mut paused_at: Option<Instant> = None;
+        // lead in by ramping up to the first values of the patterns, before their clocks start
+        while running.load(Ordering::Acquire) {
+            if self.paused.is_paused() {
+                self.hold_paused(&mut paused_at, recording, run_start)
+                    .await?;
+                self.retick(&mut interval, &mut tickrate_hz);
+                interval.tick().await;
+                continue;
+            }
+            if let Some(paused_at) = paused_at.take() {
+                paused_total += paused_at.elapsed();
+            }
+            let ramp_elapsed = run_start.elapsed().saturating_sub(paused_total);
+            if ramp_elapsed >= self.start_ramp {
+                break;
+            }
+            let progress = ramp_elapsed.as_secs_f64() / self.start_ramp.as_secs_f64();
+            let sent = self.actuate(Duration::ZERO, progress).await?;
+            record(recording, run_start.elapsed(), sent);
+            self.retick(&mut interval, &mut tickrate_hz);
+            interval.tick().await;
+        }
+        // sampling the start of stateful patterns during the ramp advances them, so start them over
+        if !self.start_ramp.is_zero() {
+            self.reset_patterns();
+        }
+
         let start = Instant::now();
-        let mut interval = interval(Duration::from_millis(1000 / self.tickrate_hz));
+        let mut last_elapsed = Duration::ZERO;
+        paused_total = Duration::ZERO;
         while running.load(Ordering::Acquire) {
-            let elapsed = start.elapsed();
-            if elapsed > self.pattern.duration() {
+            if self.paused.is_paused() {
+                self.hold_paused(&mut paused_at, recording, run_start)
+                    .await?;
+                self.retick(&mut interval, &mut tickrate_hz);
+                interval.tick().await;
+                continue;
+            }
+            if let Some(paused_at) = paused_at.take() {
+                paused_total += paused_at.elapsed();
+            }
+            // time spent paused doesn't count towards the patterns' clocks
+            let mut elapsed = start.elapsed().saturating_sub(paused_total);
+            if looping && !self.pattern.duration().is_zero() {
+                let length = self.pattern.duration().as_secs_f64();
+                elapsed = Duration::from_secs_f64(elapsed.as_secs_f64() % length);
+            } else if elapsed > self.pattern.duration() {
                 break;
             }
 
-            let global_intensity = self.pattern.sample(elapsed);
-            for device in self.buttplug.devices() {
-                let mut actuator_map: HashMap<u32, f64> = HashMap::new();
-                for actuator in device.vibrate_attributes() {
-                    // vibrate attributes returns a vec of actuator info
-                    let level = self
+            let sent = self.actuate(elapsed, 1.0).await?;
+            last_elapsed = elapsed;
+            record(recording, run_start.elapsed(), sent);
+            self.retick(&mut interval, &mut tickrate_hz);
+            interval.tick().await;
+        }
+
+        // fade out from where the patterns stopped, whether the run was cancelled or ran out,
+        // unless it was cancelled while paused and the devices are already stopped
+        let stop_start = Instant::now();
+        while paused_at.is_none() && stop_start.elapsed() < self.stop_ramp {
+            let progress = stop_start.elapsed().as_secs_f64() / self.stop_ramp.as_secs_f64();
+            let sent = self.actuate(last_elapsed, 1.0 - progress).await?;
+            record(recording, run_start.elapsed(), sent);
+            self.retick(&mut interval, &mut tickrate_hz);
+            interval.tick().await;
+        }
+        Ok(())
+    }
+
+    /// Stops every device on the first tick of a pause, noting when the pause started in `paused_at`.
+    async fn hold_paused(
+        &mut self,
+        paused_at: &mut Option<Instant>,
+        recording: &mut Option<Vec<(Duration, f64)>>,
+        run_start: Instant,
+    ) -> Result<(), ButtplugClientError> {
+        if paused_at.is_some() {
+            return Ok(());
+        }
+        *paused_at = Some(Instant::now());
+        match self.buttplug.stop_all_devices().await {
+            Ok(()) => record(recording, run_start.elapsed(), Some(0.0)),
+            Err(e) if self.error_policy == ErrorPolicy::Abort => return Err(e),
+            Err(_) => {}
+        }
+        // resend every level once resumed
+        self.last_sent.clear();
+        Ok(())
+    }
+
+    /// Resets every pattern the driver plays to its initial state.
+    fn reset_patterns(&mut self) {
+        self.pattern.reset();
+        self.device_patterns.values_mut().for_each(|p| p.reset());
+        self.actuator_patterns.values_mut().for_each(|p| p.reset());
+        match &mut self.mode {
+            Mode::Pan(pan) => pan.reset(),
+            Mode::Wave { history, .. } => history.clear(),
+            _ => {}
+        }
+    }
+
+    /// Whether `levels` differ enough from the last levels sent to `device` to be sent under
+    /// `set_min_change`, remembering them as the last levels sent if so.
+    fn changed_enough(&mut self, device: u32, levels: &HashMap<u32, f64>) -> bool {
+        let Some(epsilon) = self.min_change else {
+            return true;
+        };
+        let unchanged = self.last_sent.get(&device).is_some_and(|last| {
+            levels.iter().all(|(actuator, level)| {
+                last.get(actuator)
+                    .is_some_and(|last_level| (last_level - level).abs() <= epsilon)
+            })
+        });
+        if !unchanged {
+            self.last_sent.insert(device, levels.clone());
+        }
+        !unchanged
+    }
+
+    /// Rebuilds `interval` if the tickrate has changed from `tickrate_hz` since the last tick.
+    fn retick(&self, interval: &mut Interval, tickrate_hz: &mut u64) {
+        let current = self.tickrate_hz.get();
+        if current != *tickrate_hz {
+            *tickrate_hz = current;
+            *interval = tokio::time::interval(tick_period(current));
+        }
+    }
+
+    /// Samples all patterns at `elapsed`, scales them by `scale`, and sends the result to every device.
+    ///
+    /// Returns the highest level sent to any device, or `None` if no command was sent.
+    async fn actuate(
+        &mut self,
+        elapsed: Duration,
+        scale: f64,
+    ) -> Result<Option<f64>, ButtplugClientError> {
+        let raw_intensity = self.pattern.sample(elapsed);
+        let global_intensity = raw_intensity * scale;
+        if let Mode::Wave { history, .. } = &mut self.mode {
+            // time going back, such as a looping run starting over, starts a new history
+            if history.back().is_some_and(|(time, _)| *time > elapsed) {
+                history.clear();
+            }
+            history.push_back((elapsed, raw_intensity));
+        }
+        let ceiling = self
+            .sustained_limit
+            .as_mut()
+            .map_or(f64::INFINITY, |limit| {
+                limit.ceiling(global_intensity, Instant::now())
+            });
+        let pan = match &mut self.mode {
+            Mode::Pan(pan) => Some(pan.sample(elapsed)),
+            _ => None,
+        };
+        let mut highest_sent: Option<f64> = None;
+        let mut devices = self.buttplug.devices();
+        if let Some(filter) = &self.device_filter {
+            devices.retain(|device| filter(device));
+        }
+        devices.sort_by_key(|device| device.index());
+        let count = devices.len();
+        for (position, device) in devices.into_iter().enumerate() {
+            let weight = match &self.mode {
+                Mode::Weighted(weights) => weights.get(&device.index()).copied().unwrap_or(1.0),
+                _ => pan.map_or(1.0, |pan| pan_weight(pan, position, count)),
+            };
+            // devices further along a wave play the patterns later, and are silent until they start
+            let device_elapsed = match &self.mode {
+                Mode::Wave { step, .. } => elapsed.checked_sub(wave_delay(*step, position)),
+                _ => Some(elapsed),
+            };
+            let device_global = match (&self.mode, device_elapsed) {
+                (_, None) => 0.0,
+                (Mode::Wave { history, .. }, Some(time)) => wave_sample(history, time) * scale,
+                (_, Some(_)) => global_intensity,
+            };
+            let actuators: Vec<u32> = match self.actuator {
+                // vibrate attributes returns a vec of actuator info
+                Actuator::Vibrate => device
+                    .vibrate_attributes()
+                    .iter()
+                    .map(|actuator| *actuator.index())
+                    .collect(),
+                // rotation features are addressed by their position in the device's rotate attributes
+                Actuator::Rotate { .. } => {
+                    let count = device
+                        .message_attributes()
+                        .rotate_cmd()
+                        .as_ref()
+                        .map_or(0, |attributes| attributes.len() as u32);
+                    (0..count).collect()
+                }
+                Actuator::Linear => (0..device.linear_attributes().len() as u32).collect(),
+            };
+            // devices without the actuator are skipped rather than sent commands they would reject
+            if actuators.is_empty() {
+                continue;
+            }
+            let mut actuator_map: HashMap<u32, f64> = HashMap::new();
+            for actuator in actuators {
+                let level = match device_elapsed {
+                    Some(time) => self
                         .actuator_patterns
-                        .get_mut(&(device.index(), *actuator.index()))
-                        .map(|p| p.sample(elapsed))
+                        .get_mut(&(device.index(), actuator))
+                        .map(|p| p.sample(time) * scale)
                         .unwrap_or(
                             self.device_patterns
                                 .get_mut(&device.index())
-                                .map(|p| p.sample(elapsed))
-                                .unwrap_or(global_intensity),
-                        );
-                    actuator_map.insert(*actuator.index(), level);
+                                .map(|p| p.sample(time) * scale)
+                                .unwrap_or(device_global),
+                        ),
+                    None => 0.0,
+                };
+                let level = self
+                    .out_of_range_policy
+                    .apply((level * weight).min(ceiling));
+                actuator_map.insert(actuator, level * self.max_intensity);
+            }
+            if !self.changed_enough(device.index(), &actuator_map) {
+                continue;
+            }
+            let highest = actuator_map.values().copied().fold(0.0, f64::max);
+            let sent = match self.actuator {
+                Actuator::Vibrate => {
+                    device
+                        .vibrate(&ScalarValueCommand::ScalarValueMap(actuator_map))
+                        .await
+                }
+                Actuator::Rotate { clockwise } => {
+                    let rotate_map = actuator_map
+                        .into_iter()
+                        .map(|(actuator, level)| (actuator, (level, clockwise)))
+                        .collect();
+                    device.rotate(&RotateCommand::RotateMap(rotate_map)).await
+                }
+                Actuator::Linear => {
+                    let move_ms = tick_period(self.tickrate_hz.get()).as_millis() as u32;
+                    let linear_map = actuator_map
+                        .into_iter()
+                        .map(|(actuator, position)| (actuator, (move_ms, position)))
+                        .collect();
+                    device.linear(&LinearCommand::LinearMap(linear_map)).await
+                }
+            };
+            match sent {
+                Ok(()) => highest_sent = Some(highest_sent.map_or(highest, |h| h.max(highest))),
+                Err(e) if self.error_policy == ErrorPolicy::Abort => return Err(e),
+                // make sure the next tick resends to the device, even if its levels haven't changed
+                Err(_) => {
+                    self.last_sent.remove(&device.index());
                 }
-                device
-                    .vibrate(&ScalarValueCommand::ScalarValueMap(actuator_map))
-                    .await?;
             }
-            interval.tick().await;
         }
-        self.buttplug.stop_all_devices().await
+        if let Mode::Wave { step, history } = &mut self.mode {
+            // keep the samples the furthest device along the wave still needs
+            let oldest = elapsed.saturating_sub(wave_delay(*step, count.saturating_sub(1)));
+            while history.get(1).is_some_and(|(time, _)| *time <= oldest) {
+                history.pop_front();
+            }
+        }
+        Ok(highest_sent)
+    }
+}
+
+/// Builds a `Driver` with chained configuration, checking the settings when it is built.
+///
+/// Settings left unset keep the defaults of `Driver::new`. The remaining settings, such as device and actuator
+/// patterns, can be changed on the built driver.
+pub struct DriverBuilder {
+    tickrate_hz: u64,
+    max_intensity: f64,
+    pattern: Option<Box<dyn PatternGenerator>>,
+    device_filter: Option<DeviceFilter>,
+    out_of_range_policy: OutOfRangePolicy,
+    actuator: Actuator,
+    error_policy: ErrorPolicy,
+    start_ramp: Duration,
+    stop_ramp: Duration,
+    min_change: Option<f64>,
+}
+
+impl Default for DriverBuilder {
+    fn default() -> Self {
+        DriverBuilder {
+            tickrate_hz: DEFAULT_TICKRATE_HZ,
+            max_intensity: 1.0,
+            pattern: None,
+            device_filter: None,
+            out_of_range_policy: OutOfRangePolicy::default(),
+            actuator: Actuator::default(),
+            error_policy: ErrorPolicy::default(),
+            start_ramp: Duration::ZERO,
+            stop_ramp: Duration::ZERO,
+            min_change: None,
+        }
+    }
+}
+
+impl DriverBuilder {
+    /// Sets the tickrate of the driver, in Hz, which must not be zero. See `Driver::set_tickrate`.
+    pub fn tickrate(mut self, hz: u64) -> Self {
+        self.tickrate_hz = hz;
+        self
+    }
+
+    /// Sets the level a full intensity is sent as, which must be between 0.0 and 1.0.
+    /// See `Driver::set_max_intensity`.
+    pub fn max_intensity(mut self, max: f64) -> Self {
+        self.max_intensity = max;
+        self
+    }
+
+    /// Sets the global pattern of the driver, which must be set before building. See `Driver::set_pattern`.
+    pub fn pattern<P: 'static + PatternGenerator>(mut self, pattern: P) -> Self {
+        self.pattern = Some(Box::new(pattern));
+        self
+    }
+
+    /// Only actuates the devices for which `filter` returns true. See `Driver::set_device_filter`.
+    pub fn device_filter<F: 'static + Fn(&ButtplugClientDevice) -> bool>(
+        mut self,
+        filter: F,
+    ) -> Self {
+        self.device_filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Sets what the driver does with levels outside of the range 0.0 to 1.0.
+    /// See `Driver::set_out_of_range_policy`.
+    pub fn out_of_range_policy(mut self, policy: OutOfRangePolicy) -> Self {
+        self.out_of_range_policy = policy;
+        self
+    }
+
+    /// Sets which kind of actuator the driver sends intensities to. See `Driver::set_actuator`.
+    pub fn actuator(mut self, actuator: Actuator) -> Self {
+        self.actuator = actuator;
+        self
+    }
+
+    /// Sets what the driver does when a command to a device fails. See `Driver::set_error_policy`.
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.error_policy = policy;
+        self
+    }
+
+    /// Sets how long the driver ramps up when a run starts. See `Driver::set_start_ramp`.
+    pub fn start_ramp(mut self, ramp: Duration) -> Self {
+        self.start_ramp = ramp;
+        self
+    }
+
+    /// Sets how long the driver ramps down when a run ends. See `Driver::set_stop_ramp`.
+    pub fn stop_ramp(mut self, ramp: Duration) -> Self {
+        self.stop_ramp = ramp;
+        self
+    }
+
+    /// Skips commands that haven't changed by more than `epsilon`, which must not be negative.
+    /// See `Driver::set_min_change`.
+    pub fn min_change(mut self, epsilon: f64) -> Self {
+        self.min_change = Some(epsilon);
+        self
+    }
+
+    /// Builds the driver for the given ButtplugClient, or returns an error if a setting is invalid.
+    pub fn build(self, bp: Arc<ButtplugClient>) -> Result<Driver, BuildError> {
+        let error = |message: &str| {
+            Err(BuildError {
+                message: message.to_string(),
+            })
+        };
+        if self.tickrate_hz == 0 {
+            return error("tickrate must not be zero");
+        }
+        if !(0.0..=1.0).contains(&self.max_intensity) {
+            return error("max intensity must be between 0.0 and 1.0");
+        }
+        if self
+            .min_change
+            .is_some_and(|epsilon| epsilon.is_nan() || epsilon < 0.0)
+        {
+            return error("min change must be a non-negative number");
+        }
+        let Some(pattern) = self.pattern else {
+            return error("no pattern was set");
+        };
+        let mut driver = Driver::with_boxed_pattern(bp, pattern);
+        driver.tickrate_hz.set(self.tickrate_hz);
+        driver.max_intensity = self.max_intensity;
+        driver.device_filter = self.device_filter;
+        driver.out_of_range_policy = self.out_of_range_policy;
+        driver.actuator = self.actuator;
+        driver.error_policy = self.error_policy;
+        driver.start_ramp = self.start_ramp;
+        driver.stop_ramp = self.stop_ramp;
+        driver.min_change = self.min_change;
+        Ok(driver)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::AtomicBool, Arc, Mutex};
+    use std::time::Duration;
+
+    use buttplug::client::ButtplugClient;
+    use buttplug::core::connector::{
+        ButtplugConnector, ButtplugConnectorError, ButtplugConnectorResultFuture,
+    };
+    use buttplug::core::message::{
+        ButtplugClientMessageV3, ButtplugDeviceMessage, ButtplugMessage, ButtplugMessageFinalizer,
+        ButtplugMessageSpecVersion, ButtplugServerMessageV3, DeviceListV3, ErrorCode, ErrorV0,
+        OkV0, SensorReadingV3, ServerInfoV2,
+    };
+    use tokio::sync::mpsc::Sender;
+
+    use super::*;
+    use crate::shapes::{Constant, Linear};
+    use crate::transformers::RealtimeSync;
+
+    type Received = Arc<Mutex<Vec<ButtplugClientMessageV3>>>;
+
+    /// Connector standing in for a server with a list of devices, logging every message the client sends.
+    ///
+    /// Each device is described by the JSON of its message attributes and the battery level, out of 100,
+    /// reported by its battery sensor. Device commands are answered with an error if `failing` is set,
+    /// and with `Ok` otherwise.
+    struct MockConnector {
+        devices: Vec<(serde_json::Value, i32)>,
+        failing: bool,
+        received: Received,
+        sender: Option<Sender<ButtplugServerMessageV3>>,
+    }
+
+    impl ButtplugConnector<ButtplugClientMessageV3, ButtplugServerMessageV3> for MockConnector {
+        fn connect(
+            &mut self,
+            sender: Sender<ButtplugServerMessageV3>,
+        ) -> ButtplugConnectorResultFuture {
+            self.sender = Some(sender);
+            Box::pin(async { Ok(()) })
+        }
+
+        fn disconnect(&self) -> ButtplugConnectorResultFuture {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn send(&self, message: ButtplugClientMessageV3) -> ButtplugConnectorResultFuture {
+            let id = message.id();
+            let mut reply: ButtplugServerMessageV3 = match &message {
+                ButtplugClientMessageV3::RequestServerInfo(_) => {
+                    ServerInfoV2::new("mock", ButtplugMessageSpecVersion::Version3, 0).into()
+                }
+                ButtplugClientMessageV3::RequestDeviceList(_) => {
+                    let devices = self
+                        .devices
+                        .iter()
+                        .enumerate()
+                        .map(|(index, (attributes, _))| {
+                            serde_json::from_value(serde_json::json!({
+                                "DeviceIndex": index,
+                                "DeviceName": "Mock",
+                                "DeviceMessages": attributes,
+                            }))
+                            .unwrap()
+                        })
+                        .collect();
+                    let mut list = DeviceListV3::new(devices);
+                    list.finalize();
+                    list.into()
+                }
+                ButtplugClientMessageV3::SensorReadCmd(read) => SensorReadingV3::new(
+                    read.device_index(),
+                    *read.sensor_index(),
+                    *read.sensor_type(),
+                    vec![self.devices[read.device_index() as usize].1],
+                )
+                .into(),
+                _ if self.failing => {
+                    ErrorV0::new(ErrorCode::ErrorDevice, "mock failure", None).into()
+                }
+                _ => OkV0::new(id).into(),
+            };
+            reply.set_id(id);
+            self.received.lock().unwrap().push(message);
+            let sender = self.sender.clone().unwrap();
+            Box::pin(async move {
+                sender
+                    .send(reply)
+                    .await
+                    .map_err(|_| ButtplugConnectorError::ConnectorNotConnected)
+            })
+        }
+    }
+
+    /// Connects a client to a `MockConnector` with a device with the given message attributes,
+    /// returning it with the messages it sends from then on.
+    async fn mock_client(
+        attributes: serde_json::Value,
+        failing: bool,
+    ) -> (Arc<ButtplugClient>, Received) {
+        mock_devices(vec![(attributes, 100)], failing).await
+    }
+
+    /// Connects a client to a `MockConnector` with the given devices, like `mock_client`.
+    async fn mock_devices(
+        devices: Vec<(serde_json::Value, i32)>,
+        failing: bool,
+    ) -> (Arc<ButtplugClient>, Received) {
+        let received = Received::default();
+        let client = Arc::new(ButtplugClient::new("test"));
+        let count = devices.len();
+        let connector = MockConnector {
+            devices,
+            failing,
+            received: received.clone(),
+            sender: None,
+        };
+        client.connect(connector).await.unwrap();
+        // the devices are added by the client's event loop after the handshake
+        while client.devices().len() < count {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        received.lock().unwrap().clear();
+        (client, received)
+    }
+
+    fn paused_driver(policy: ErrorPolicy) -> Driver {
+        // the client is never connected, so stopping its devices fails
+        let client = Arc::new(ButtplugClient::new("test"));
+        let mut driver = Driver::new(client, Constant::new(0.5, Duration::from_secs(1)));
+        driver.set_error_policy(policy).pause();
+        driver
+    }
+
+    #[tokio::test]
+    async fn pausing_aborts_on_stop_errors_by_default() {
+        let mut driver = paused_driver(ErrorPolicy::Abort);
+        let run = tokio::time::timeout(Duration::from_secs(1), driver.run()).await;
+        assert!(matches!(run, Ok(Err(_))));
+    }
+
+    #[tokio::test]
+    async fn pausing_follows_continue_policy_on_stop_errors() {
+        let mut driver = paused_driver(ErrorPolicy::Continue);
+        let run = tokio::time::timeout(
+            Duration::from_millis(300),
+            driver.run_while(Arc::new(AtomicBool::new(true))),
+        )
+        .await;
+        // still held paused rather than ended by the failed stop
+        assert!(run.is_err());
+    }
+
+    #[test]
+    fn builder_applies_configured_values() {
+        let client = Arc::new(ButtplugClient::new("test"));
+        let driver = Driver::builder()
+            .tickrate(25)
+            .max_intensity(0.6)
+            .pattern(Constant::new(0.5, Duration::from_secs(1)))
+            .device_filter(|device| device.index() == 1)
+            .out_of_range_policy(OutOfRangePolicy::Silence)
+            .error_policy(ErrorPolicy::Continue)
+            .build(client)
+            .unwrap();
+        assert_eq!(driver.tickrate(), 25);
+        assert_eq!(driver.max_intensity, 0.6);
+        assert!(driver.device_filter.is_some());
+        assert_eq!(driver.out_of_range_policy, OutOfRangePolicy::Silence);
+        assert_eq!(driver.error_policy, ErrorPolicy::Continue);
+    }
+
+    #[test]
+    fn builder_rejects_invalid_settings() {
+        let client = Arc::new(ButtplugClient::new("test"));
+        let pattern = || Constant::new(0.5, Duration::from_secs(1));
+        assert!(Driver::builder()
+            .tickrate(0)
+            .pattern(pattern())
+            .build(client.clone())
+            .is_err());
+        assert!(Driver::builder()
+            .max_intensity(1.5)
+            .pattern(pattern())
+            .build(client.clone())
+            .is_err());
+        assert!(Driver::builder().build(client).is_err());
+    }
+
+    #[test]
+    fn out_of_range_policies() {
+        assert_eq!(OutOfRangePolicy::Clamp.apply(1.5), 1.0);
+        assert_eq!(OutOfRangePolicy::Clamp.apply(f64::NAN), 0.0);
+        assert_eq!(OutOfRangePolicy::Silence.apply(1.5), 0.0);
+        assert_eq!(OutOfRangePolicy::Silence.apply(0.25), 0.25);
+    }
+
+    #[test]
+    fn wave_delay_saturates_instead_of_overflowing() {
+        let step = Duration::from_millis(250);
+        assert_eq!(wave_delay(step, 3), Duration::from_millis(750));
+        assert_eq!(wave_delay(Duration::MAX, 2), Duration::MAX);
+        assert_eq!(wave_delay(step, usize::MAX), Duration::MAX);
+    }
+
+    /// Logs every sample and reset of a pattern, to check how the driver plays it.
+    struct Probe(Arc<Mutex<Vec<Option<Duration>>>>);
+
+    impl PatternGenerator for Probe {
+        fn sample(&mut self, time: Duration) -> f64 {
+            self.0.lock().unwrap().push(Some(time));
+            0.5
+        }
+
+        fn duration(&self) -> Duration {
+            Duration::from_millis(150)
+        }
+
+        fn reset(&mut self) {
+            self.0.lock().unwrap().push(None);
+        }
+    }
+
+    #[tokio::test]
+    async fn start_ramp_restarts_patterns_before_playback() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let client = Arc::new(ButtplugClient::new("test"));
+        let mut driver = Driver::new(client, Probe(log.clone()));
+        driver.set_start_ramp(Duration::from_millis(250));
+        // stopping the devices of the unconnected client fails once the run is over
+        let _ = driver.run().await;
+        let log = log.lock().unwrap();
+        // reset at the start of the run, and again once the ramp is over
+        assert_eq!(log[0], None);
+        let ramp_end = log.iter().skip(1).position(Option::is_none).unwrap() + 1;
+        let ramp = &log[1..ramp_end];
+        assert!(ramp.len() > 1, "ramp sampled the pattern {log:?}");
+        assert!(ramp.iter().all(|event| *event == Some(Duration::ZERO)));
+        assert!(log[ramp_end + 1..].iter().all(Option::is_some));
+    }
+
+    #[test]
+    fn record_only_keeps_sent_levels() {
+        let mut recording = Some(Vec::new());
+        record(&mut recording, Duration::ZERO, Some(0.25));
+        record(&mut recording, Duration::from_millis(100), None);
+        assert_eq!(recording, Some(vec![(Duration::ZERO, 0.25)]));
+        let mut not_recording = None;
+        record(&mut not_recording, Duration::ZERO, Some(0.25));
+        assert_eq!(not_recording, None);
+    }
+
+    #[tokio::test]
+    async fn recording_without_devices_writes_no_actions() {
+        let path = std::env::temp_dir().join(format!(
+            "buttplug_patterns_recording_{}.funscript",
+            std::process::id()
+        ));
+        let client = Arc::new(ButtplugClient::new("test"));
+        let mut driver = Driver::new(client, Constant::new(0.5, Duration::from_millis(200)));
+        driver.record_to_funscript(&path);
+        // stopping the devices of the unconnected client fails, but the recording is still written
+        assert!(driver.run().await.is_err());
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(written.contains(r#""actions":[]"#), "{written}");
+    }
+
+    #[test]
+    fn min_change_only_sends_changed_levels() {
+        let client = Arc::new(ButtplugClient::new("test"));
+        let mut driver = Driver::new(client, Constant::new(0.5, Duration::MAX));
+        let levels = |level| HashMap::from([(0, level)]);
+        // every command is sent by default
+        assert!(driver.changed_enough(0, &levels(0.5)));
+        assert!(driver.changed_enough(0, &levels(0.5)));
+
+        driver.set_min_change(0.01);
+        let sent = (0..10)
+            .filter(|_| driver.changed_enough(0, &levels(0.5)))
+            .count();
+        assert_eq!(sent, 1);
+        assert!(driver.changed_enough(0, &levels(0.6)));
+        assert!(!driver.changed_enough(0, &levels(0.605)));
+        // other devices are tracked separately
+        assert!(driver.changed_enough(1, &levels(0.6)));
+    }
+
+    #[test]
+    fn pan_moves_the_peak_across_devices() {
+        let mut pan = Linear::new(0.0, 1.0, Duration::from_secs(1));
+        let loudest: Vec<usize> = [0, 500, 1000]
+            .into_iter()
+            .map(|millis| {
+                let pan = pan.sample(Duration::from_millis(millis));
+                (0..3)
+                    .max_by(|a, b| pan_weight(pan, *a, 3).total_cmp(&pan_weight(pan, *b, 3)))
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(loudest, [0, 1, 2]);
+        // halfway between two devices they share the intensity
+        assert_eq!(pan_weight(0.25, 0, 3), 0.5);
+        assert_eq!(pan_weight(0.25, 1, 3), 0.5);
+        assert_eq!(pan_weight(0.25, 2, 3), 0.0);
+        assert_eq!(pan_weight(0.7, 0, 1), 1.0);
+    }
+
+    #[tokio::test]
+    async fn looping_restarts_the_pattern_after_its_duration() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let client = Arc::new(ButtplugClient::new("test"));
+        let mut driver = Driver::new(client, Probe(log.clone()));
+        let run = tokio::time::timeout(
+            Duration::from_millis(500),
+            driver.run_looping(Arc::new(AtomicBool::new(true))),
+        )
+        .await;
+        // still looping well past the pattern's duration
+        assert!(run.is_err());
+        let times: Vec<Duration> = log.lock().unwrap().iter().flatten().copied().collect();
+        assert!(times.iter().all(|time| *time < Duration::from_millis(150)));
+        let restarts = times.windows(2).filter(|pair| pair[1] < pair[0]).count();
+        assert!(restarts >= 2, "{times:?}");
+    }
+
+    #[tokio::test]
+    async fn looping_stops_on_the_flag() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let client = Arc::new(ButtplugClient::new("test"));
+        let mut driver = Driver::new(client, Probe(log.clone()));
+        let run = tokio::time::timeout(
+            Duration::from_millis(500),
+            driver.run_looping(Arc::new(AtomicBool::new(false))),
+        )
+        .await;
+        assert!(run.is_ok());
+        assert!(log.lock().unwrap().iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn rendered_commands_are_spaced_by_the_tick_period() {
+        let mut ramp = Linear::new(0.0, 1.5, Duration::from_secs(1));
+        let commands = render_commands(&mut ramp, 20);
+        assert_eq!(commands.len(), 21);
+        for (tick, (time, level)) in commands.into_iter().enumerate() {
+            assert_eq!(time, Duration::from_millis(tick as u64 * 50));
+            assert_eq!(level, ramp.sample(time).min(1.0));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn tickrate_changes_during_a_run() {
+        struct Ticks(Arc<Mutex<Vec<Duration>>>);
+
+        impl PatternGenerator for Ticks {
+            fn sample(&mut self, time: Duration) -> f64 {
+                self.0.lock().unwrap().push(time);
+                0.5
+            }
+
+            fn duration(&self) -> Duration {
+                Duration::MAX
+            }
+        }
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let client = Arc::new(ButtplugClient::new("test"));
+        let mut driver = Driver::new(client, Ticks(log.clone()));
+        driver.set_tickrate_live(10);
+        let handle = driver.tickrate_handle();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(450)).await;
+            handle.set(100);
+        });
+        let _ = tokio::time::timeout(Duration::from_millis(900), driver.run()).await;
+        let log = log.lock().unwrap();
+        let gaps: Vec<Duration> = log.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        // the first tick of an interval fires immediately, including the interval rebuilt at the next tick
+        let mut expected = vec![Duration::ZERO];
+        expected.extend([Duration::from_millis(100); 5]);
+        expected.push(Duration::ZERO);
+        expected.extend([Duration::from_millis(10); 40]);
+        assert_eq!(gaps, expected);
+    }
+
+    #[test]
+    fn crowd_weight_follows_the_device_count() {
+        let weights: Vec<f64> = (0..6).map(|count| crowd_weight(count, 3)).collect();
+        assert_eq!(weights, [0.0, 0.0, 0.5, 1.0, 1.0, 1.0]);
+        assert_eq!(crowd_weight(1, 1), 1.0);
+
+        // without any connected devices only the solo pattern plays
+        let client = Arc::new(ButtplugClient::new("test"));
+        let mut driver = Driver::new(client, Constant::new(0.0, Duration::MAX));
+        driver.set_crowd_pattern(
+            Constant::new(0.3, Duration::from_secs(1)),
+            Constant::new(0.9, Duration::from_secs(2)),
+            3,
+        );
+        assert_eq!(driver.pattern.sample(Duration::ZERO), 0.3);
+        assert_eq!(driver.pattern.duration(), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn sustained_limit_ramps_down_after_max_on() {
+        let client = Arc::new(ButtplugClient::new("test"));
+        let mut driver = Driver::new(client, Constant::new(1.0, Duration::MAX));
+        driver.set_sustained_limit(0.8, Duration::from_millis(100), 0.3);
+        let limit = driver.sustained_limit.as_mut().unwrap();
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        assert_eq!(limit.ceiling(1.0, start), f64::INFINITY);
+        assert_eq!(limit.ceiling(1.0, at(99)), f64::INFINITY);
+        // the ramp down starts once the intensity has been high for `max_on`
+        assert_eq!(limit.ceiling(1.0, at(100)), 1.0);
+        assert!((limit.ceiling(1.0, at(600)) - 0.65).abs() < 1e-9);
+        assert!((limit.ceiling(1.0, at(1100)) - 0.3).abs() < 1e-9);
+        assert!((limit.ceiling(1.0, at(5000)) - 0.3).abs() < 1e-9);
+        // dropping below the threshold lifts the limit
+        assert_eq!(limit.ceiling(0.5, at(5100)), f64::INFINITY);
+        assert_eq!(limit.ceiling(1.0, at(5200)), f64::INFINITY);
+    }
+
+    #[tokio::test]
+    async fn battery_weighted_runs_favor_charged_devices() {
+        let vibrator = serde_json::json!({
+            "ScalarCmd": [{"ActuatorType": "Vibrate", "StepCount": 20}],
+        });
+        let mut battery = vibrator.clone();
+        battery["SensorReadCmd"] = serde_json::json!([{
+            "FeatureDescriptor": "Battery",
+            "SensorType": "Battery",
+            "SensorRange": [[0, 100]],
+        }]);
+        let devices = vec![(battery.clone(), 25), (battery, 75), (vibrator, 0)];
+        let (client, received) = mock_devices(devices, false).await;
+        let mut driver = Driver::new(client, Constant::new(0.0, Duration::ZERO));
+        driver
+            .run_battery_weighted(Constant::new(0.4, Duration::from_millis(100)))
+            .await
+            .unwrap();
+        // the global pattern from before the run is back
+        assert_eq!(driver.pattern.duration(), Duration::ZERO);
+        let received = received.lock().unwrap();
+        let mut levels: HashMap<u32, Vec<f64>> = HashMap::new();
+        for message in received.iter() {
+            if let ButtplugClientMessageV3::ScalarCmd(scalar) = message {
+                for command in scalar.scalars() {
+                    levels
+                        .entry(scalar.device_index())
+                        .or_default()
+                        .push(command.scalar());
+                }
+            }
+        }
+        // the device without a battery sensor gets the average charge of the others
+        for (device, expected) in [(0, 0.2), (1, 0.6), (2, 0.4)] {
+            assert!(!levels[&device].is_empty());
+            assert!(
+                levels[&device]
+                    .iter()
+                    .all(|level| (level - expected).abs() < 1e-9),
+                "device {device}: {:?}",
+                levels[&device]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn ping_pong_alternates_between_two_devices() {
+        let vibrator = serde_json::json!({
+            "ScalarCmd": [{"ActuatorType": "Vibrate", "StepCount": 20}],
+        });
+        let devices = vec![(vibrator.clone(), 100), (vibrator, 100)];
+        let (client, received) = mock_devices(devices, false).await;
+        let mut driver = Driver::new(client, Constant::new(0.0, Duration::ZERO));
+        driver.set_tickrate(50);
+        driver
+            .run_ping_pong(Constant::new(0.6, Duration::from_millis(800)), 5.0)
+            .await
+            .unwrap();
+        assert_eq!(driver.pattern.duration(), Duration::ZERO);
+        let received = received.lock().unwrap();
+        let mut playing = Vec::new();
+        for message in received.iter() {
+            if let ButtplugClientMessageV3::ScalarCmd(scalar) = message {
+                for command in scalar.scalars() {
+                    let level = command.scalar();
+                    assert!(level == 0.0 || (level - 0.6).abs() < 1e-9, "{level}");
+                    if level > 0.0 {
+                        playing.push(scalar.device_index());
+                    }
+                }
+            }
+        }
+        // swapping every 200ms, so the 800ms pattern is split into four turns
+        assert_eq!(playing.first(), Some(&0));
+        playing.dedup();
+        assert!(playing.len() >= 4, "{playing:?}");
+        assert!(playing.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+
+    #[tokio::test]
+    async fn recording_epsilon_merges_constant_sections() {
+        let attributes = serde_json::json!({
+            "ScalarCmd": [{"ActuatorType": "Vibrate", "StepCount": 20}],
+        });
+        let (client, _) = mock_client(attributes, false).await;
+        let path = std::env::temp_dir().join(format!(
+            "buttplug_patterns_merged_{}.funscript",
+            std::process::id()
+        ));
+        let mut driver = Driver::new(client, Constant::new(0.5, Duration::from_millis(300)));
+        driver.record_to_funscript(&path).set_tickrate(50);
+        driver.run().await.unwrap();
+        let every_tick = std::fs::read_to_string(&path)
+            .unwrap()
+            .matches(r#""at":"#)
+            .count();
+        driver.set_recording_epsilon(0.01);
+        driver.run().await.unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(every_tick > 10, "{every_tick}");
+        // the whole run is one constant section, kept as its first and last action
+        assert_eq!(written.matches(r#""at":"#).count(), 2, "{written}");
+        assert_eq!(written.matches(r#""pos":50"#).count(), 2, "{written}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stopping_a_run_early_writes_the_recording() {
+        let attributes = serde_json::json!({
+            "ScalarCmd": [{"ActuatorType": "Vibrate", "StepCount": 20}],
+        });
+        let (client, received) = mock_client(attributes, false).await;
+        let path = std::env::temp_dir().join(format!(
+            "buttplug_patterns_stopped_{}.funscript",
+            std::process::id()
+        ));
+        let mut driver = Driver::new(client, Constant::new(0.5, Duration::MAX));
+        driver.record_to_funscript(&path);
+        let running = Arc::new(AtomicBool::new(true));
+        let stop = running.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(450)).await;
+            stop.store(false, Ordering::Release);
+        });
+        driver.run_while(running).await.unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        // the interval's first tick is immediate, so the start is sent twice, then once every 100ms up to 400ms
+        assert_eq!(written.matches(r#""at":"#).count(), 6, "{written}");
+        assert!(written.contains(r#"{"at":400,"pos":50}]"#), "{written}");
+        assert!(received
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|message| matches!(message, ButtplugClientMessageV3::StopAllDevices(_))));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn realtime_sync_resumes_where_the_driver_paused() {
+        let attributes = serde_json::json!({
+            "ScalarCmd": [{"ActuatorType": "Vibrate", "StepCount": 20}],
+        });
+        let (client, _) = mock_client(attributes, false).await;
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let clock = || Instant::now().into_std();
+        let synced =
+            RealtimeSync::with_clock(Probe(log.clone()), Duration::from_millis(300), clock);
+        let mut driver = Driver::new(client, synced);
+        let pause = driver.pause_handle();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(55)).await;
+            pause.pause();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            pause.resume();
+        });
+        driver.run().await.unwrap();
+        let times: Vec<Duration> = log.lock().unwrap().iter().flatten().copied().collect();
+        // the inner pattern carries on a tick at a time, rather than skipping the paused second
+        assert!(times.len() > 2, "{times:?}");
+        assert!(
+            times
+                .windows(2)
+                .all(|pair| pair[1] - pair[0] <= Duration::from_millis(100)),
+            "{times:?}"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wave_replays_the_global_pattern_down_the_line() {
+        /// A ramp that logs the times it's sampled at.
+        struct LoggedRamp(Arc<Mutex<Vec<Duration>>>);
+
+        impl PatternGenerator for LoggedRamp {
+            fn sample(&mut self, time: Duration) -> f64 {
+                self.0.lock().unwrap().push(time);
+                time.as_secs_f64()
+            }
+
+            fn duration(&self) -> Duration {
+                Duration::from_millis(900)
+            }
+        }
+
+        let vibrator = serde_json::json!({
+            "ScalarCmd": [{"ActuatorType": "Vibrate", "StepCount": 20}],
+        });
+        let devices = vec![
+            (vibrator.clone(), 100),
+            (vibrator.clone(), 100),
+            (vibrator, 100),
+        ];
+        let (client, received) = mock_devices(devices, false).await;
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut driver = Driver::new(client, LoggedRamp(log.clone()));
+        driver.run_wave(Duration::from_millis(200)).await.unwrap();
+        let mut levels: HashMap<u32, Vec<f64>> = HashMap::new();
+        for message in received.lock().unwrap().iter() {
+            if let ButtplugClientMessageV3::ScalarCmd(scalar) = message {
+                for command in scalar.scalars() {
+                    levels
+                        .entry(scalar.device_index())
+                        .or_default()
+                        .push(command.scalar());
+                }
+            }
+        }
+        // each device plays the first device's levels two ticks later, and is silent until then
+        let first = &levels[&0];
+        assert!(first.len() > 5, "{levels:?}");
+        for (device, delay) in [(1, 2), (2, 4)] {
+            let delayed = &levels[&device];
+            assert_eq!(delayed.len(), first.len());
+            assert!(
+                delayed[..delay].iter().all(|level| *level == 0.0),
+                "{levels:?}"
+            );
+            assert_eq!(delayed[delay..], first[..first.len() - delay], "{levels:?}");
+        }
+        // the global pattern is sampled once a tick, never back in time
+        let log = log.lock().unwrap();
+        assert_eq!(log.len(), first.len());
+        assert!(log.windows(2).all(|pair| pair[0] <= pair[1]), "{log:?}");
+    }
+
+    /// Runs a `Probe` on a mock device with a start ramp, pausing for a second after `pause_after` if set,
+    /// returning the probe's log, the messages sent, and the number of messages sent while paused.
+    async fn paused_probe_run(
+        pause_after: Option<Duration>,
+    ) -> (Vec<Option<Duration>>, Vec<ButtplugClientMessageV3>, usize) {
+        let attributes = serde_json::json!({
+            "ScalarCmd": [{"ActuatorType": "Vibrate", "StepCount": 20}],
+        });
+        let (client, received) = mock_client(attributes, false).await;
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut driver = Driver::new(client, Probe(log.clone()));
+        driver.set_start_ramp(Duration::from_millis(300));
+        let pause = driver.pause_handle();
+        let sent = received.clone();
+        let paused = tokio::spawn(async move {
+            let Some(pause_after) = pause_after else {
+                return 0;
+            };
+            tokio::time::sleep(pause_after).await;
+            pause.pause();
+            // give the driver a tick to stop the devices
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let sent_before = sent.lock().unwrap().len();
+            tokio::time::sleep(Duration::from_millis(900)).await;
+            pause.resume();
+            sent.lock().unwrap().len() - sent_before
+        });
+        driver.run().await.unwrap();
+        let log = log.lock().unwrap().clone();
+        let received = received.lock().unwrap().clone();
+        (log, received, paused.await.unwrap())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pausing_continues_the_pattern_where_it_stopped() {
+        let (unpaused, _, _) = paused_probe_run(None).await;
+        // paused between ticks, 50ms after the ramp
+        let (log, received, sent_while_paused) =
+            paused_probe_run(Some(Duration::from_millis(355))).await;
+        assert_eq!(log, unpaused);
+        assert_eq!(sent_while_paused, 0);
+        let times: Vec<Duration> = log.iter().flatten().copied().collect();
+        assert!(
+            times
+                .windows(2)
+                .all(|pair| pair[1] - pair[0] <= Duration::from_millis(100)),
+            "{times:?}"
+        );
+        let stops = received
+            .iter()
+            .filter(|message| matches!(message, ButtplugClientMessageV3::StopAllDevices(_)))
+            .count();
+        // one stop for the pause and one at the end of the run
+        assert_eq!(stops, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pausing_holds_the_start_ramp() {
+        let (unpaused, unpaused_received, _) = paused_probe_run(None).await;
+        let (log, received, sent_while_paused) =
+            paused_probe_run(Some(Duration::from_millis(155))).await;
+        // the ramp takes as many ticks as without the pause, and sends nothing while paused
+        assert_eq!(log, unpaused);
+        assert_eq!(sent_while_paused, 0);
+        let commands = |messages: &[ButtplugClientMessageV3]| {
+            messages
+                .iter()
+                .filter(|message| matches!(message, ButtplugClientMessageV3::ScalarCmd(_)))
+                .count()
+        };
+        assert_eq!(commands(&received), commands(&unpaused_received));
+    }
+
+    #[tokio::test]
+    async fn rotation_sends_the_sampled_speed() {
+        let attributes = serde_json::json!({
+            "RotateCmd": [{"ActuatorType": "Rotate", "StepCount": 20}],
+        });
+        let (client, received) = mock_client(attributes, false).await;
+        let mut driver = Driver::new(client, Constant::new(0.4, Duration::from_millis(100)));
+        driver.set_actuator(Actuator::Rotate { clockwise: true });
+        driver.run().await.unwrap();
+        let received = received.lock().unwrap();
+        let rotations: Vec<(f64, bool)> = received
+            .iter()
+            .filter_map(|message| match message {
+                ButtplugClientMessageV3::RotateCmd(rotate) => Some(rotate.rotations().clone()),
+                _ => None,
+            })
+            .flatten()
+            .map(|rotation| (rotation.speed(), rotation.clockwise()))
+            .collect();
+        assert!(!rotations.is_empty());
+        assert!(rotations.iter().all(|rotation| *rotation == (0.4, true)));
+    }
+
+    #[tokio::test]
+    async fn devices_without_the_actuator_are_skipped() {
+        let attributes = serde_json::json!({
+            "ScalarCmd": [{"ActuatorType": "Vibrate", "StepCount": 20}],
+        });
+        let (client, received) = mock_client(attributes, false).await;
+        let mut driver = Driver::new(client, Constant::new(0.4, Duration::from_millis(100)));
+        driver.set_actuator(Actuator::Rotate { clockwise: false });
+        driver.run().await.unwrap();
+        // only the final stop is sent
+        let received = received.lock().unwrap();
+        assert!(matches!(
+            received[..],
+            [ButtplugClientMessageV3::StopAllDevices(_)]
+        ));
+    }
+
+    #[tokio::test]
+    async fn linear_moves_to_the_sampled_position_over_a_tick() {
+        let attributes = serde_json::json!({
+            "LinearCmd": [{"ActuatorType": "Position", "StepCount": 100}],
+        });
+        let (client, received) = mock_client(attributes, false).await;
+        let mut driver = Driver::new(client, Constant::new(0.6, Duration::from_millis(200)));
+        driver.set_actuator(Actuator::Linear).set_tickrate(20);
+        driver.run().await.unwrap();
+        let received = received.lock().unwrap();
+        let moves: Vec<(f64, u32)> = received
+            .iter()
+            .filter_map(|message| match message {
+                ButtplugClientMessageV3::LinearCmd(linear) => Some(linear.vectors().clone()),
+                _ => None,
+            })
+            .flatten()
+            .map(|vector| (vector.position(), vector.duration()))
+            .collect();
+        assert!(!moves.is_empty());
+        assert!(moves.iter().all(|command| *command == (0.6, 50)));
+    }
+
+    async fn failing_run(policy: ErrorPolicy) -> (Result<(), DriverError>, usize) {
+        let attributes = serde_json::json!({
+            "ScalarCmd": [{"ActuatorType": "Vibrate", "StepCount": 20}],
+        });
+        let (client, received) = mock_client(attributes, true).await;
+        let mut driver = Driver::new(client, Constant::new(0.4, Duration::from_millis(200)));
+        driver.set_error_policy(policy).set_tickrate(50);
+        let result = driver.run().await;
+        let commands = received
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|message| matches!(message, ButtplugClientMessageV3::ScalarCmd(_)))
+            .count();
+        (result, commands)
+    }
+
+    #[tokio::test]
+    async fn failed_commands_follow_the_error_policy() {
+        let (result, commands) = failing_run(ErrorPolicy::Abort).await;
+        assert!(matches!(result, Err(DriverError::Buttplug(_))));
+        assert_eq!(commands, 1);
+        // carries on through every tick, and only the failed final stop is reported
+        let (result, commands) = failing_run(ErrorPolicy::Continue).await;
+        assert!(result.is_err());
+        assert!(commands > 5, "{commands}");
+    }
+
+    #[tokio::test]
+    async fn runs_end_by_stopping_every_device() {
+        let attributes = serde_json::json!({
+            "ScalarCmd": [{"ActuatorType": "Vibrate", "StepCount": 20}],
+        });
+        let (client, received) = mock_client(attributes, false).await;
+        let mut driver = Driver::new(client, Constant::new(0.8, Duration::from_millis(200)));
+        driver
+            .set_tickrate(50)
+            .set_stop_ramp(Duration::from_millis(200));
+        driver.run().await.unwrap();
+        let received = received.lock().unwrap();
+        assert!(matches!(
+            received.last(),
+            Some(ButtplugClientMessageV3::StopAllDevices(_))
+        ));
+        let levels: Vec<f64> = received
+            .iter()
+            .filter_map(|message| match message {
+                ButtplugClientMessageV3::ScalarCmd(scalar) => Some(scalar.scalars()[0].scalar()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(levels[0], 0.8);
+        // the ramp fades out from the last level before the devices are stopped
+        assert!(*levels.last().unwrap() < 0.2, "{levels:?}");
+        assert!(
+            levels.windows(2).all(|pair| pair[1] <= pair[0]),
+            "{levels:?}"
+        );
     }
 }
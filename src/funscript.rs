@@ -0,0 +1,186 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+use crate::PatternGenerator;
+
+/// Writes a list of `(time, intensity)` actions to `writer` in the funscript format.
+///
+/// Intensities are clamped to 0.0 to 1.0 and scaled to funscript positions of 0 to 100.
+pub fn write_funscript<W: Write>(mut writer: W, actions: &[(Duration, f64)]) -> io::Result<()> {
+    write!(
+        writer,
+        r#"{{"version":"1.0","inverted":false,"range":100,"actions":["#
+    )?;
+    for (i, (at, intensity)) in actions.iter().enumerate() {
+        if i > 0 {
+            write!(writer, ",")?;
+        }
+        let pos = (intensity.clamp(0.0, 1.0) * 100.0).round() as u8;
+        write!(writer, r#"{{"at":{},"pos":{}}}"#, at.as_millis(), pos)?;
+    }
+    writeln!(writer, "]}}")?;
+    writer.flush()
+}
+
+/// Writes a list of `(time, intensity)` actions to a funscript file at `path`.
+pub fn save_funscript(path: impl AsRef<Path>, actions: &[(Duration, f64)]) -> io::Result<()> {
+    write_funscript(BufWriter::new(File::create(path)?), actions)
+}
+
+/// Merges runs of consecutive actions within `epsilon` of the first action of the run, keeping only the first
+/// and last action of each run as anchors, so long constant sections don't need an action per tick.
+///
+/// The merged actions replay the same shape, holding each run at the level of its first action.
+pub fn merge_actions(actions: &[(Duration, f64)], epsilon: f64) -> Vec<(Duration, f64)> {
+    let mut merged = Vec::new();
+    let mut run: Option<((Duration, f64), (Duration, f64))> = None;
+    for &action in actions {
+        match run {
+            Some((first, _)) if (action.1 - first.1).abs() <= epsilon => {
+                run = Some((first, (action.0, first.1)));
+            }
+            _ => {
+                if let Some((first, last)) = run.replace((action, action)) {
+                    merged.push(first);
+                    if last != first {
+                        merged.push(last);
+                    }
+                }
+            }
+        }
+    }
+    if let Some((first, last)) = run {
+        merged.push(first);
+        if last != first {
+            merged.push(last);
+        }
+    }
+    merged
+}
+
+/// Linearly interpolates between `(time, value)` points sorted by time, holding the first and last values
+/// outside of them.
+pub(crate) fn interpolate(points: &[(Duration, f64)], time: Duration) -> f64 {
+    let next = points.partition_point(|(at, _)| *at <= time);
+    match (next.checked_sub(1).map(|i| points[i]), points.get(next)) {
+        (Some((from_at, from)), Some(&(to_at, to))) => {
+            let progress = (time - from_at).as_secs_f64() / (to_at - from_at).as_secs_f64();
+            from + (to - from) * progress
+        }
+        (Some((_, last)), None) => last,
+        (None, Some(&(_, first))) => first,
+        (None, None) => 0.0,
+    }
+}
+
+/// Plays back the actions of a funscript, linearly interpolating between them.
+///
+/// Positions of 0 to 100 are played as intensities of 0.0 to 1.0, and drive the position of linear
+/// actuators when the driver is set to `Actuator::Linear`. Before the first action the first position is held,
+/// and the pattern ends at the last action, holding its position if sampled after it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Funscript {
+    actions: Vec<(Duration, f64)>,
+}
+
+impl Funscript {
+    /// Creates a funscript pattern from a list of `(time, intensity)` actions, sorted by time on creation.
+    pub fn from_actions(mut actions: Vec<(Duration, f64)>) -> Self {
+        actions.sort_by_key(|(at, _)| *at);
+        Funscript { actions }
+    }
+
+    /// Parses a funscript from its JSON contents.
+    #[cfg(feature = "serde")]
+    pub fn from_json(json: &str) -> io::Result<Self> {
+        #[derive(serde::Deserialize)]
+        struct Action {
+            at: u64,
+            pos: f64,
+        }
+        #[derive(serde::Deserialize)]
+        struct Script {
+            actions: Vec<Action>,
+        }
+        let script: Script = serde_json::from_str(json).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid funscript: {e}"),
+            )
+        })?;
+        Ok(Self::from_actions(
+            script
+                .actions
+                .into_iter()
+                .map(|action| (Duration::from_millis(action.at), action.pos / 100.0))
+                .collect(),
+        ))
+    }
+
+    /// Loads a funscript file from `path`.
+    #[cfg(feature = "serde")]
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+}
+
+impl PatternGenerator for Funscript {
+    fn sample(&mut self, time: Duration) -> f64 {
+        interpolate(&self.actions, time)
+    }
+
+    fn duration(&self) -> Duration {
+        self.actions.last().map_or(Duration::ZERO, |(at, _)| *at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn actions_are_interpolated_and_held_at_the_ends() {
+        let mut script = Funscript::from_actions(vec![
+            (Duration::from_millis(1000), 1.0),
+            (Duration::from_millis(500), 0.2),
+            (Duration::from_millis(1500), 0.0),
+        ]);
+        assert_eq!(script.duration(), Duration::from_millis(1500));
+        assert_near(script.sample(Duration::ZERO), 0.2);
+        assert_near(script.sample(Duration::from_millis(500)), 0.2);
+        assert_near(script.sample(Duration::from_millis(750)), 0.6);
+        assert_near(script.sample(Duration::from_millis(1000)), 1.0);
+        assert_near(script.sample(Duration::from_millis(1100)), 0.8);
+        assert_near(script.sample(Duration::from_millis(1500)), 0.0);
+        assert_near(script.sample(Duration::from_millis(3000)), 0.0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn funscripts_parse_from_json() {
+        let json = r#"{"version":"1.0","actions":[{"at":0,"pos":0},{"at":400,"pos":100},{"at":600,"pos":50}]}"#;
+        let mut script = Funscript::from_json(json).unwrap();
+        assert_eq!(script.duration(), Duration::from_millis(600));
+        assert_near(script.sample(Duration::ZERO), 0.0);
+        assert_near(script.sample(Duration::from_millis(100)), 0.25);
+        assert_near(script.sample(Duration::from_millis(400)), 1.0);
+        assert_near(script.sample(Duration::from_millis(500)), 0.75);
+        assert_near(script.sample(Duration::from_millis(900)), 0.5);
+
+        let mut written = Vec::new();
+        write_funscript(&mut written, &script.actions).unwrap();
+        let reloaded = Funscript::from_json(std::str::from_utf8(&written).unwrap()).unwrap();
+        assert_eq!(reloaded, script);
+
+        let error = Funscript::from_json(r#"{"actions":[{"at":0}]}"#).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
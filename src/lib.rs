@@ -1,7 +1,21 @@
+/// Tools for inspecting and validating patterns.
+pub mod analysis;
 /// Driver to run a pattern on a buttplug device
 pub mod driver;
+/// Reading and writing funscript files.
+pub mod funscript;
+/// Serializable trees describing composed patterns.
+pub mod node;
+mod ops;
+/// Building patterns from playlists of presets.
+#[cfg(feature = "serde")]
+pub mod playlist;
 /// Patterns that generate random values.
 pub mod random;
+/// Patterns that read intensity values from external sources.
+pub mod reader;
+#[cfg(feature = "serde")]
+mod secs;
 /// Patterns that generate basic shapes and waves.
 pub mod shapes;
 /// Patterns that transform other patterns.
@@ -9,13 +23,27 @@ pub mod shapes;
 /// Note: most transformers should not be used directly, but through methods on the `Pattern` trait.
 pub mod transformers;
 
-pub use driver::Driver;
+pub use driver::{
+    render_commands, Actuator, BuildError, Driver, DriverBuilder, DriverError, ErrorPolicy,
+    OutOfRangePolicy, PauseHandle, TickrateHandle,
+};
 
-use std::time::Duration;
+use std::{
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
 
+use analysis::PatternWarnings;
+use random::SmoothNoise;
+use shapes::{Constant, Pause, SineWave};
 use transformers::*;
 
 /// Represents a pattern to be used to actuate buttplug devices.
+///
+/// Every pattern in the crate implements this one trait with the same signatures, so any of them can be
+/// boxed as a `Box<dyn PatternGenerator>` and stored or chained together.
+/// Sampling takes `&mut self` because many patterns keep state between samples, such as random patterns
+/// and smoothing transformers, and times are `Duration`s measured from the start of the pattern.
 pub trait PatternGenerator {
     /// Gives an intensity value for a given time.
     ///
@@ -24,12 +52,116 @@ pub trait PatternGenerator {
     /// `.repeat()`, `.forever()`, and `.chain()` methods of `Pattern` for extending Patterns
     fn sample(&mut self, time: Duration) -> f64;
 
-    /// how long a cycle of the pattern takes in seconds
+    /// How long the pattern takes to play, or `Duration::MAX` if it never ends.
     fn duration(&self) -> Duration;
 
+    /// How long a single loop of the pattern takes.
+    ///
+    /// This is the same as `duration` for patterns that don't loop. Repeated patterns, and patterns that
+    /// loop forever, report the length of the looped cycle instead.
+    fn period(&self) -> Duration {
+        self.duration()
+    }
+
     /// Resets the pattern to its initial state if it is stateful.
     /// if the pattern is stateless, this method does nothing.
     fn reset(&mut self) {}
+
+    /// The patterns this pattern is built from, so a whole pattern tree can be inspected.
+    ///
+    /// Shapes and other patterns that don't wrap another pattern have none.
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        Vec::new()
+    }
+
+    /// Estimates the slope of the pattern at `time`, in intensity per second,
+    /// from the difference between samples at `time` and `time + dt`.
+    fn slope(&mut self, time: Duration, dt: Duration) -> f64 {
+        let start = self.sample(time);
+        (self.sample(time + dt) - start) / dt.as_secs_f64()
+    }
+
+    /// Samples the pattern from the start to the end of its duration at `tickrate_hz` samples per second.
+    ///
+    /// Infinite patterns are rendered for a single period. Patterns without a finite period render nothing,
+    /// as do tickrates that aren't positive and finite, and renders that would take more than `MAX_SAMPLES`.
+    fn render(&mut self, tickrate_hz: f64) -> Vec<f64> {
+        let Some(count) = finite_length(self).and_then(|length| sample_count(length, tickrate_hz))
+        else {
+            return Vec::new();
+        };
+        (0..count)
+            .map(|tick| self.sample(Duration::from_secs_f64(tick as f64 / tickrate_hz)))
+            .collect()
+    }
+
+    /// Integrates the intensity of the pattern over its duration using the trapezoidal rule,
+    /// taking `resolution_hz` samples per second.
+    ///
+    /// A constant 0.5 pattern lasting 4 seconds delivers an energy of 2.0.
+    /// Infinite patterns are integrated over a single period. Patterns without a finite period
+    /// have no meaningful energy and return NaN, as do resolutions that aren't positive and finite.
+    fn total_energy(&mut self, resolution_hz: f64) -> f64 {
+        let Some(length) = finite_length(self) else {
+            return f64::NAN;
+        };
+        let Some(count) = sample_count(length, resolution_hz) else {
+            return f64::NAN;
+        };
+        let count = count.max(1);
+        let dt = length.as_secs_f64() / count as f64;
+        let mut previous = self.sample(Duration::ZERO);
+        let mut energy = 0.0;
+        for tick in 1..=count {
+            let value = self.sample(Duration::from_secs_f64(tick as f64 * dt));
+            energy += (previous + value) / 2.0 * dt;
+            previous = value;
+        }
+        energy
+    }
+}
+
+/// The stretch of time that covers a pattern once: its duration, or a single period for infinite
+/// patterns. Returns `None` when neither is finite, since nothing of that length can be sampled.
+pub(crate) fn finite_length<P: PatternGenerator + ?Sized>(pattern: &P) -> Option<Duration> {
+    [pattern.duration(), pattern.period()]
+        .into_iter()
+        .find(|length| *length != Duration::MAX)
+}
+
+/// The most samples `render` takes of a pattern, about four and a half hours at 1000 Hz.
+pub const MAX_SAMPLES: usize = 1 << 24;
+
+/// The number of samples covering `length` at `rate_hz` samples per second, or `None` if the rate isn't
+/// a positive, finite number or there would be more than `MAX_SAMPLES`.
+pub(crate) fn sample_count(length: Duration, rate_hz: f64) -> Option<usize> {
+    if !rate_hz.is_finite() || rate_hz <= 0.0 {
+        return None;
+    }
+    let count = (length.as_secs_f64() * rate_hz).round();
+    (count <= MAX_SAMPLES as f64).then_some(count as usize)
+}
+
+impl<P: PatternGenerator + ?Sized> PatternGenerator for Box<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        (**self).sample(time)
+    }
+
+    fn duration(&self) -> Duration {
+        (**self).duration()
+    }
+
+    fn period(&self) -> Duration {
+        (**self).period()
+    }
+
+    fn reset(&mut self) {
+        (**self).reset()
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        (**self).subpatterns()
+    }
 }
 
 impl<T: PatternGenerator> Pattern for T {}
@@ -38,11 +170,17 @@ impl<T: PatternGenerator> Pattern for T {}
 /// `Pattern`s,
 ///
 /// Patterns can be run on a device using a `Driver`
+///
+/// Patterns from this crate can also be combined with operators, `a + b`, `a - b` and `a * b`
+/// are the same as `a.sum(b)`, `a.subtract(b)` and `a.multiply(b)`.
+/// When the right hand side is an `f64` instead of a pattern, `a * 2.0` scales the intensity like
+/// `a.scale_intensity(2.0)`, and `a + 0.5` or `a - 0.5` offsets it like `a.offset(0.5)` or `a.offset(-0.5)`.
+/// There are no scalar operators for time, use `scale_time` and `shift` for that.
 pub trait Pattern: PatternGenerator + Sized {
     /// Scales the pattern in the time domain by a given `scalar`.
     ///
-    /// For example, a scalar of 2.0 would double the length of cycles.
-    /// This would turn a sine wave of wavelength 0.5 seconds into a square wave of wavelength 1.0 seconds.
+    /// For example, a scalar of 2.0 would double the length of cycles, and of the whole pattern.
+    /// This would turn a sine wave of wavelength 0.5 seconds into a sine wave of wavelength 1.0 seconds.
     fn scale_time(self, scalar: f64) -> ScaleTime<Self> {
         ScaleTime {
             pattern: self,
@@ -61,6 +199,16 @@ pub trait Pattern: PatternGenerator + Sized {
         }
     }
 
+    /// Offsets the intensity of the pattern by a constant `offset`.
+    ///
+    /// For example, an offset of 0.25 would turn a sine wave between 0.0 and 0.5 into one between 0.25 and 0.75.
+    fn offset(self, offset: f64) -> Offset<Self> {
+        Offset {
+            pattern: self,
+            offset,
+        }
+    }
+
     /// Takes the sum of two patterns.
     ///
     /// For example, a sine wave of amplitude 0.5 and a square wave of amplitude 0.5 would sum to a sine wave of amplitude 1.0.
@@ -82,6 +230,20 @@ pub trait Pattern: PatternGenerator + Sized {
         Average { a: self, b: other }
     }
 
+    /// Takes the lower of two patterns at each point in time, keeping the pattern under another as an envelope.
+    ///
+    /// For example, a sine wave of amplitude 1.0 and a constant 0.5 would give a sine wave with its peaks clipped at 0.5.
+    fn min_with<Q: Pattern>(self, other: Q) -> Min<Self, Q> {
+        Min { a: self, b: other }
+    }
+
+    /// Takes the higher of two patterns at each point in time, keeping the pattern above another as a floor.
+    ///
+    /// For example, a sine wave of amplitude 1.0 and a constant 0.5 would give a sine wave with its troughs raised to 0.5.
+    fn max_with<Q: Pattern>(self, other: Q) -> Max<Self, Q> {
+        Max { a: self, b: other }
+    }
+
     /// Clamps the pattern to a given range.
     ///
     /// This is useful for limiting the output of a pattern to a certain range.
@@ -107,7 +269,9 @@ pub trait Pattern: PatternGenerator + Sized {
         ValidScale { pattern: self }
     }
 
-    // Time shifts a pattern by `time_shift` seconds, can be used to skip a portion of a pattern
+    /// Time shifts a pattern forward by `time_shift`, can be used to skip a portion of a pattern.
+    ///
+    /// The duration is shortened by the shift, but never below zero.
     fn shift(self, time_shift: Duration) -> Shift<Self> {
         Shift {
             pattern: self,
@@ -128,7 +292,7 @@ pub trait Pattern: PatternGenerator + Sized {
         Forever { pattern: self }
     }
 
-    /// Chains two patterns together, `other` is run after `self`'s duration.
+    /// Chains two patterns together, `other` is run from its start after `self`'s duration.
     fn chain<Q: Pattern>(self, other: Q) -> Chain<Self, Q> {
         Chain {
             first: self,
@@ -136,7 +300,8 @@ pub trait Pattern: PatternGenerator + Sized {
         }
     }
 
-    /// Chains two patterns together with a linear crossfade between them.
+    /// Chains two patterns together with a linear crossfade between them, with `other` starting from its
+    /// start `overlap` before the end of `self`.
     fn crossfade<Q: Pattern>(self, other: Q, overlap: Duration) -> Crossfade<Self, Q> {
         Crossfade {
             first: self,
@@ -152,6 +317,347 @@ pub trait Pattern: PatternGenerator + Sized {
             modulator,
         }
     }
+
+    /// Modulates the timing of the pattern by another pattern, shifting it by the modulator's value in seconds.
+    fn modulate_frequency<M: Pattern>(self, modulator: M) -> FrequencyModulator<Self, M> {
+        FrequencyModulator {
+            pattern: self,
+            modulator,
+        }
+    }
+
+    /// Limits how quickly the rate of change of the pattern can change, in intensity per second squared.
+    ///
+    /// This smooths harsh onsets into S-shaped curves, useful for linear actuators.
+    fn accel_limit(self, max_accel: f64) -> AccelLimit<Self> {
+        AccelLimit::new(self, max_accel)
+    }
+
+    /// Applies a tremolo with a given `wavelength` to the pattern, where the depth of the tremolo is
+    /// controlled by the `depth` pattern at each instant.
+    fn dynamic_tremolo<D: Pattern>(
+        self,
+        depth: D,
+        wavelength: Duration,
+    ) -> DynamicTremolo<Self, D> {
+        DynamicTremolo {
+            carrier: self,
+            depth,
+            lfo: SineWave::new(1.0, wavelength),
+        }
+    }
+
+    /// Fades the pattern out to 0.0 over the final `tail_fraction` of its duration, for a natural ending.
+    ///
+    /// For example, a `tail_fraction` of 0.25 fades out over the last quarter of the pattern.
+    /// Has no effect on patterns that loop forever.
+    fn auto_tail(self, tail_fraction: f64) -> AutoTail<Self> {
+        AutoTail {
+            pattern: self,
+            tail_fraction,
+        }
+    }
+
+    /// Crushes the pattern to `levels` intensity steps, holding each value for `hold`, for a harsh lo-fi effect.
+    fn crush(self, levels: u32, hold: Duration) -> Crush<Self> {
+        Crush {
+            pattern: self,
+            levels,
+            hold,
+        }
+    }
+
+    /// Quantizes the pattern to `steps` evenly spaced levels between 0.0 and 1.0, such as 2 for on and off.
+    fn quantize(self, steps: u32) -> Quantize<Self> {
+        Quantize {
+            pattern: self,
+            steps,
+        }
+    }
+
+    /// Checks the pattern for composition mistakes before it is used, by sampling a copy of it
+    /// at `resolution_hz` samples per second over its duration, one period if it loops forever,
+    /// or its first second if it never repeats.
+    ///
+    /// Reports zero durations, zero durations or periods anywhere in the pattern tree such as zero wavelengths,
+    /// NaN or infinite values, and values outside of the valid range 0.0 to 1.0.
+    fn validate(&self, resolution_hz: f64) -> Result<(), PatternWarnings>
+    where
+        Self: Clone,
+    {
+        let warnings = PatternWarnings::check(&mut self.clone(), resolution_hz);
+        if warnings.is_empty() {
+            Ok(())
+        } else {
+            Err(warnings)
+        }
+    }
+
+    /// Smooths the pattern with a moving average over `window`, taking `samples` samples per average.
+    fn moving_average(self, window: Duration, samples: u32) -> MovingAverage<Self> {
+        MovingAverage {
+            pattern: self,
+            window,
+            samples,
+        }
+    }
+
+    /// Imposes the amplitude envelope of `amplitude` onto the shape of this pattern,
+    /// scanning `samples` samples over `window` to find the envelopes.
+    fn cross_synth<A: Pattern>(
+        self,
+        amplitude: A,
+        window: Duration,
+        samples: u32,
+    ) -> CrossSynth<A, Self> {
+        CrossSynth {
+            amplitude,
+            shape: self,
+            window,
+            samples,
+        }
+    }
+
+    /// Chains two patterns together with `gap` of silence between them.
+    fn chain_with_gap<Q: Pattern>(self, gap: Duration, other: Q) -> Chain<Chain<Self, Pause>, Q> {
+        self.chain(Pause::new(gap)).chain(other)
+    }
+
+    /// Mirrors the pattern's intensity around `axis`, giving `2 * axis - value`.
+    ///
+    /// For example, reflecting a constant 0.3 around an axis of 0.5 gives 0.7.
+    /// Reflected values may leave the valid range, use `.clamp_valid()` afterwards to keep them in it.
+    fn reflect(self, axis: f64) -> Reflect<Self> {
+        Reflect {
+            pattern: self,
+            axis,
+        }
+    }
+
+    /// Adds `accent` on top of the pattern only while the pattern is rising, for emphasis.
+    fn rising_accent<A: Pattern>(self, accent: A) -> RisingAccent<Self, A> {
+        RisingAccent::new(self, accent)
+    }
+
+    /// Guarantees rest periods, forcing the pattern to 0.0 for `min_off` whenever it has stayed above
+    /// `off_threshold` for `max_on`.
+    ///
+    /// Useful for toys that overheat when run for too long.
+    fn enforce_rest(
+        self,
+        max_on: Duration,
+        min_off: Duration,
+        off_threshold: f64,
+    ) -> EnforceRest<Self> {
+        EnforceRest::new(self, max_on, min_off, off_threshold)
+    }
+
+    /// Overlays a slow breathing envelope on the pattern, so its intensity gently swells and recedes
+    /// once every `period`.
+    ///
+    /// A `depth` of 0.0 leaves the pattern unchanged, a `depth` of 1.0 fades it all the way out at each breath.
+    fn breathe(self, period: Duration, depth: f64) -> DynamicTremolo<Self, Constant> {
+        self.dynamic_tremolo(Constant::new(depth, Duration::MAX), period)
+    }
+
+    /// Plays the pattern once as an intro, then loops `body` forever.
+    ///
+    /// Unlike `.chain(body.forever())`, the length of the loop stays queryable through `Intro::loop_period`.
+    fn intro<B: Pattern>(self, body: B) -> Intro<Self, B> {
+        Intro { intro: self, body }
+    }
+
+    /// Ducks the pattern under `trigger`, so the pattern is turned down while `trigger` is high.
+    ///
+    /// A `depth` of 1.0 silences the pattern completely when `trigger` is at 1.0.
+    fn duck<T: Pattern>(self, trigger: T, depth: f64) -> Duck<Self, T> {
+        Duck {
+            background: self,
+            trigger,
+            depth,
+        }
+    }
+
+    /// Snaps any output below `threshold` to 0.0, silencing the hum some motors make at very low intensities.
+    ///
+    /// If `snap_high` is true, output above `1.0 - threshold` is also snapped up to 1.0.
+    fn dead_zone(self, threshold: f64, snap_high: bool) -> DeadZone<Self> {
+        DeadZone {
+            pattern: self,
+            threshold,
+            snap_high,
+        }
+    }
+
+    /// Expands the dynamics of the pattern for a punchier feel, raising its intensity to the power `gamma`.
+    ///
+    /// For example, a `gamma` of 2.0 turns 0.5 into 0.25 while leaving 1.0 unchanged.
+    fn expand(self, gamma: f64) -> Expand<Self> {
+        Expand {
+            pattern: self,
+            gamma,
+        }
+    }
+
+    /// Randomly replaces the last `fill_fraction` of each cycle of the pattern with `fill`, with the given
+    /// `probability`, using a seeded random number generator.
+    ///
+    /// Cycles are one `period` of the pattern, so this is most useful with `.repeat()` or `.forever()` patterns.
+    fn fill<F: Pattern>(
+        self,
+        fill: F,
+        probability: f64,
+        fill_fraction: f64,
+        seed: u64,
+    ) -> Fill<Self, F> {
+        Fill::new(self, fill, probability, fill_fraction, seed)
+    }
+
+    /// Warps the timing within each cycle of the pattern through an easing curve.
+    fn ease_time(self, kind: EaseKind) -> EaseTime<Self> {
+        EaseTime {
+            pattern: self,
+            kind,
+        }
+    }
+
+    /// Remaps the intensity of the pattern through an easing curve.
+    fn ease(self, kind: EaseKind) -> Ease<Self> {
+        Ease {
+            pattern: self,
+            kind,
+        }
+    }
+
+    /// Sums `voices` copies of the pattern, each delayed by `spread` more than the last.
+    fn chorus(self, voices: u32, spread: Duration) -> Chorus<Self>
+    where
+        Self: Clone,
+    {
+        Chorus::new(self, voices, spread)
+    }
+
+    /// Uses the pattern as a control that flips the output between `level_a` and `level_b` each time
+    /// it crosses `midpoint`.
+    fn toggle_on_cross(self, level_a: f64, level_b: f64, midpoint: f64) -> ToggleOnCross<Self> {
+        ToggleOnCross::new(self, level_a, level_b, midpoint)
+    }
+
+    /// Samples the pattern `lookahead` ahead of the current time.
+    fn look_ahead(self, lookahead: Duration) -> LookAhead<Self> {
+        LookAhead {
+            pattern: self,
+            lookahead,
+        }
+    }
+
+    /// Limits the pattern to at most `ceiling`, turning it down up to `lookahead` before peaks.
+    fn limit(self, ceiling: f64, lookahead: Duration) -> Limiter<Self> {
+        Limiter::new(self, ceiling, lookahead)
+    }
+
+    /// Boosts the pattern by `boost` while `trigger` is set, fading back out over `release` once it is cleared.
+    fn punch_in(self, trigger: Arc<AtomicBool>, boost: f64, release: Duration) -> PunchIn<Self> {
+        PunchIn::new(self, trigger, boost, release)
+    }
+
+    /// Holds the peaks of the pattern, letting the output fall by at most `decay_rate` per second between them.
+    fn peak_follower(self, decay_rate: f64) -> PeakFollower<Self> {
+        PeakFollower::new(self, decay_rate)
+    }
+
+    /// Finishes building a pattern, keeping it in the valid range and boxing it so it's ready for a `Driver`.
+    ///
+    /// Like `clamp_valid`, but NaN and infinite values, such as those from zero wavelength waves, are
+    /// replaced with 0.0 instead of being passed on to the devices.
+    fn finalize(self) -> Box<dyn PatternGenerator>
+    where
+        Self: 'static,
+    {
+        Box::new(Sanitize { pattern: self })
+    }
+
+    /// Plays the pattern for `length`, ramping its speed from `start_factor` to `end_factor` times its normal rate.
+    fn accelerando(
+        self,
+        start_factor: f64,
+        end_factor: f64,
+        length: Duration,
+    ) -> Accelerando<Self> {
+        Accelerando::new(self, start_factor, end_factor, length)
+    }
+
+    /// Previews what the pattern will output at each of `times`, in seconds, without advancing it.
+    ///
+    /// Stateful patterns are snapshotted by sampling a copy, so the preview of patterns that depend on
+    /// earlier samples, such as smoothing or random patterns, may differ from what the original plays.
+    /// Negative and invalid times are treated as zero.
+    fn peek(&self, times: &[f64]) -> Vec<f64>
+    where
+        Self: Clone,
+    {
+        let mut pattern = self.clone();
+        times
+            .iter()
+            .map(|&time| pattern.sample(Duration::try_from_secs_f64(time).unwrap_or_default()))
+            .collect()
+    }
+
+    /// Slowly drifts the amplitude of the pattern at random, multiplying it by smooth noise that wanders
+    /// between `1.0 - depth` and `1.0 + depth`, picking a new direction roughly `rate` times per second.
+    ///
+    /// The drift is seeded with `seed`, so the same seed always drifts the same way.
+    fn drift_amplitude(
+        self,
+        rate: f64,
+        depth: f64,
+        seed: u64,
+    ) -> AmplitudeModulator<Self, Offset<SmoothNoise>> {
+        let feature_size = Duration::try_from_secs_f64(1.0 / rate).unwrap_or(Duration::MAX);
+        self.multiply(SmoothNoise::new(2.0 * depth, feature_size, seed).offset(1.0 - depth))
+    }
+
+    /// Freezes the output at its current value while `hold` is set, resuming where it left off once it is cleared.
+    fn freeze_on_hold(self, hold: Arc<AtomicBool>) -> FreezeOnHold<Self> {
+        FreezeOnHold::new(self, hold)
+    }
+
+    /// Inverts the pattern within the range 0.0 to `max`, so a value `v` becomes `max - v`.
+    ///
+    /// This is the same as reflecting the pattern around the middle of the range, turning a saw wave
+    /// into a reversed saw wave.
+    fn invert(self, max: f64) -> Invert<Self> {
+        Invert { pattern: self, max }
+    }
+
+    /// Loops the last `slice` of the pattern while `trigger` is set, for a stuttering beat repeat effect.
+    fn beat_repeat(self, trigger: Arc<AtomicBool>, slice: Duration) -> BeatRepeat<Self> {
+        BeatRepeat::new(self, trigger, slice)
+    }
+
+    /// Bakes the pattern into a table of `samples_per_second` samples per second, so it can be played back cheaply.
+    ///
+    /// Fails if the pattern has no finite period or the rate isn't positive.
+    fn bake(self, samples_per_second: f64) -> Result<Baked, BakeError> {
+        Baked::bake(self, samples_per_second)
+    }
+
+    /// Keeps the pattern in step with wall time, absorbing jumps in the sampled time larger than `tolerance`.
+    fn realtime_sync(self, tolerance: Duration) -> RealtimeSync<Self> {
+        RealtimeSync::new(self, tolerance)
+    }
+
+    /// Iterates over the pattern's samples at `tickrate_hz` samples per second, from the start to the end of its duration.
+    fn samples(self, tickrate_hz: f64) -> PatternIter<Self> {
+        let count = (self.duration() != Duration::MAX)
+            .then(|| (self.duration().as_secs_f64() * tickrate_hz).round() as u64);
+        PatternIter {
+            pattern: self,
+            tickrate_hz,
+            tick: 0,
+            count,
+        }
+    }
 }
 
 /// Can be used to make simple custom patterns.
@@ -172,3 +678,195 @@ impl PatternGenerator for CustomPattern {
         (self.duration)()
     }
 }
+
+/// Iterates over the samples of a pattern at a fixed tickrate, yielding `(elapsed, value)` pairs.
+///
+/// Created with `Pattern::samples`. Finite patterns yield the same samples as `render`, and infinite
+/// patterns never end, so limit them with `take` or `take_while`.
+#[derive(Clone, Debug)]
+pub struct PatternIter<P: Pattern> {
+    pattern: P,
+    tickrate_hz: f64,
+    tick: u64,
+    count: Option<u64>,
+}
+
+impl<P: Pattern> Iterator for PatternIter<P> {
+    type Item = (Duration, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.tickrate_hz <= 0.0 || self.count.is_some_and(|count| self.tick >= count) {
+            return None;
+        }
+        let elapsed = Duration::try_from_secs_f64(self.tick as f64 / self.tickrate_hz).ok()?;
+        self.tick += 1;
+        Some((elapsed, self.pattern.sample(elapsed)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::shapes::{Constant, Linear, SineWave};
+    use crate::{analysis, render_commands};
+    use crate::{Pattern, PatternGenerator};
+
+    fn looping_sine() -> impl Pattern {
+        SineWave::new(1.0, Duration::from_secs(1)).forever()
+    }
+
+    #[test]
+    fn render_of_infinite_wrapper_covers_one_period() {
+        let mut pattern = looping_sine().reflect(0.5);
+        assert_eq!(pattern.period(), Duration::from_secs(1));
+        assert_eq!(pattern.render(10.0).len(), 10);
+    }
+
+    #[test]
+    fn total_energy_of_infinite_wrapper_is_finite() {
+        let energy = looping_sine()
+            .shift(Duration::from_millis(250))
+            .total_energy(100.0);
+        assert!((energy - 0.5).abs() < 1e-3, "energy was {energy}");
+    }
+
+    #[test]
+    fn binary_ops_keep_the_longer_period() {
+        let slow = SineWave::new(1.0, Duration::from_secs(2)).forever();
+        let pattern = looping_sine().sum(slow);
+        assert_eq!(pattern.period(), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn chain_into_infinite_pattern_reports_its_period() {
+        let pattern = Constant::new(0.5, Duration::from_secs(3)).chain(looping_sine());
+        assert_eq!(pattern.duration(), Duration::MAX);
+        assert_eq!(pattern.period(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn pattern_without_finite_period_renders_nothing() {
+        let mut pattern = Constant::new(0.5, Duration::MAX).reflect(0.5);
+        assert!(pattern.render(10.0).is_empty());
+        assert!(pattern.total_energy(10.0).is_nan());
+        assert!(render_commands(&mut pattern, 10).is_empty());
+        assert!(analysis::spectrum(&mut pattern, 10.0, 4)
+            .iter()
+            .all(|bin| *bin == 0.0));
+    }
+
+    #[test]
+    fn render_rejects_invalid_and_huge_tickrates() {
+        let mut pattern = SineWave::new(1.0, Duration::from_secs(1));
+        for tickrate in [0.0, -1.0, f64::NAN, f64::INFINITY, 1e12] {
+            assert!(pattern.render(tickrate).is_empty());
+        }
+        for resolution in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(pattern.total_energy(resolution).is_nan());
+        }
+        assert_eq!(pattern.render(1000.0).len(), 1000);
+    }
+
+    #[test]
+    fn slope_of_a_linear_ramp_is_rise_over_run() {
+        let mut ramp = Linear::new(0.2, 0.8, Duration::from_secs(2));
+        for millis in [0, 500, 1500] {
+            let slope = ramp.slope(Duration::from_millis(millis), Duration::from_millis(10));
+            assert!((slope - 0.3).abs() < 1e-9);
+        }
+        let slope =
+            Constant::new(0.5, Duration::MAX).slope(Duration::ZERO, Duration::from_millis(10));
+        assert_eq!(slope, 0.0);
+    }
+
+    #[test]
+    fn total_energy_of_a_constant_is_level_times_duration() {
+        let mut pattern = Constant::new(0.5, Duration::from_secs(4));
+        assert!((pattern.total_energy(100.0) - 2.0).abs() < 1e-6);
+        let mut ramp = Linear::new(0.0, 1.0, Duration::from_secs(2));
+        assert!((ramp.total_energy(100.0) - 1.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn boxed_generators_share_one_sample_signature() {
+        let mut generators: Vec<Box<dyn PatternGenerator>> = vec![
+            Box::new(Constant::new(0.5, Duration::from_secs(1))),
+            Box::new(Linear::new(0.0, 1.0, Duration::from_secs(1))),
+            Box::new(SineWave::new(1.0, Duration::from_secs(1)).scale_intensity(0.5)),
+            Box::new(crate::random::RandomEvery::new(
+                0.0..1.0,
+                Duration::from_secs(1),
+                0.1,
+            )),
+        ];
+        let time = Duration::from_millis(500);
+        let samples: Vec<f64> = generators
+            .iter_mut()
+            .map(|generator| generator.sample(time))
+            .collect();
+        assert_eq!(samples[..3], [0.5, 0.5, 0.5]);
+        assert!((0.0..1.0).contains(&samples[3]));
+    }
+
+    #[test]
+    fn finalize_silences_nan_and_clamps_the_rest() {
+        let mut broken = SineWave::new(1.0, Duration::ZERO);
+        let time = Duration::from_millis(250);
+        assert!(!broken.sample(time).is_finite());
+        let mut finalized = broken.scale_intensity(2.0).finalize();
+        assert_eq!(finalized.sample(time), 0.0);
+        let mut loud = Linear::new(-0.5, 1.5, Duration::from_secs(2)).finalize();
+        assert_eq!(loud.sample(Duration::ZERO), 0.0);
+        assert!((loud.sample(Duration::from_millis(1250)) - 0.75).abs() < 1e-9);
+        assert_eq!(loud.sample(Duration::from_secs(2)), 1.0);
+    }
+
+    #[test]
+    fn peek_matches_samples_without_advancing() {
+        let pattern = SineWave::new(0.8, Duration::from_millis(700));
+        let times: Vec<f64> = (0..50).map(|tick| tick as f64 * 0.03).collect();
+        let peeked = pattern.peek(&times);
+        let mut played = pattern;
+        for (value, &time) in peeked.iter().zip(&times) {
+            assert_eq!(*value, played.sample(Duration::from_secs_f64(time)));
+        }
+        assert_eq!(pattern.peek(&times), peeked);
+        assert_eq!(pattern.peek(&[-1.0]), vec![pattern.peek(&[0.0])[0]]);
+    }
+
+    #[test]
+    fn drift_amplitude_stays_within_its_depth() {
+        let steady = Constant::new(0.5, Duration::from_secs(10));
+        let mut drifting = steady.drift_amplitude(2.0, 0.2, 7);
+        let mut replay = steady.drift_amplitude(2.0, 0.2, 7);
+        let (mut low, mut high) = (f64::MAX, f64::MIN);
+        for tick in 0..1000 {
+            let time = Duration::from_millis(tick * 10);
+            let value = drifting.sample(time);
+            assert_eq!(value, replay.sample(time));
+            let gain = value / 0.5;
+            assert!((0.8 - 1e-9..=1.2 + 1e-9).contains(&gain), "gain {gain}");
+            low = low.min(gain);
+            high = high.max(gain);
+        }
+        // twenty features over ten seconds wander across most of the range
+        assert!(high - low > 0.2);
+    }
+
+    #[test]
+    fn samples_iterate_at_the_tickrate() {
+        let ramp = Linear::new(0.0, 1.0, Duration::from_secs(1));
+        let samples: Vec<_> = ramp.samples(10.0).collect();
+        assert_eq!(samples.len(), 10);
+        let mut direct = ramp;
+        for (tick, &(elapsed, value)) in samples.iter().enumerate() {
+            assert_eq!(elapsed, Duration::from_millis(tick as u64 * 100));
+            assert_eq!(value, direct.sample(elapsed));
+        }
+        let looping: Vec<_> = looping_sine().samples(20.0).take(100).collect();
+        assert_eq!(looping.len(), 100);
+        assert_eq!(looping[99].0, Duration::from_millis(4950));
+        assert_eq!(ramp.samples(0.0).count(), 0);
+    }
+}
@@ -0,0 +1,729 @@
+use std::{error::Error, fmt, time::Duration};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::shapes::{Constant, Linear, Pause, Pulse, SawWave, SineWave, SquareWave, TriangleWave};
+use crate::{Pattern, PatternGenerator};
+
+/// A tree of shapes and transformers describing a whole pattern, which can be built into a generator
+/// with `build`.
+///
+/// The transformers of `Pattern` are generic, so a composed pattern's type depends on how it was built.
+/// `PatternNode` describes the same compositions as a single type, so they can be stored, loaded,
+/// and edited at runtime. With the `serde` feature, nodes are serialized with a `type` tag naming the
+/// shape or transformer, and durations in seconds.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum PatternNode {
+    Pause {
+        #[cfg_attr(feature = "serde", serde(with = "crate::secs"))]
+        duration: Duration,
+    },
+    Constant {
+        level: f64,
+        #[cfg_attr(feature = "serde", serde(with = "crate::secs"))]
+        duration: Duration,
+    },
+    Linear {
+        from: f64,
+        to: f64,
+        #[cfg_attr(feature = "serde", serde(with = "crate::secs"))]
+        duration: Duration,
+    },
+    Saw {
+        amplitude: f64,
+        #[cfg_attr(feature = "serde", serde(with = "crate::secs"))]
+        wavelength: Duration,
+    },
+    Triangle {
+        amplitude: f64,
+        #[cfg_attr(feature = "serde", serde(with = "crate::secs"))]
+        wavelength: Duration,
+    },
+    Square {
+        amplitude: f64,
+        #[cfg_attr(feature = "serde", serde(with = "crate::secs"))]
+        wavelength: Duration,
+    },
+    Sine {
+        amplitude: f64,
+        #[cfg_attr(feature = "serde", serde(with = "crate::secs"))]
+        wavelength: Duration,
+    },
+    Pulse {
+        amplitude: f64,
+        #[cfg_attr(feature = "serde", serde(with = "crate::secs"))]
+        wavelength: Duration,
+        duty: f64,
+    },
+    ScaleTime {
+        pattern: Box<PatternNode>,
+        scalar: f64,
+    },
+    ScaleIntensity {
+        pattern: Box<PatternNode>,
+        scalar: f64,
+    },
+    Offset {
+        pattern: Box<PatternNode>,
+        offset: f64,
+    },
+    Clamp {
+        pattern: Box<PatternNode>,
+        floor: f64,
+        ceiling: f64,
+    },
+    ClampValid {
+        pattern: Box<PatternNode>,
+    },
+    ScaleValid {
+        pattern: Box<PatternNode>,
+    },
+    Shift {
+        pattern: Box<PatternNode>,
+        #[cfg_attr(feature = "serde", serde(with = "crate::secs"))]
+        time_shift: Duration,
+    },
+    Repeat {
+        pattern: Box<PatternNode>,
+        count: f64,
+    },
+    Forever {
+        pattern: Box<PatternNode>,
+    },
+    Chain {
+        first: Box<PatternNode>,
+        then: Box<PatternNode>,
+    },
+    Crossfade {
+        first: Box<PatternNode>,
+        then: Box<PatternNode>,
+        #[cfg_attr(feature = "serde", serde(with = "crate::secs"))]
+        overlap_duration: Duration,
+    },
+    Sum {
+        a: Box<PatternNode>,
+        b: Box<PatternNode>,
+    },
+    Subtract {
+        a: Box<PatternNode>,
+        b: Box<PatternNode>,
+    },
+    Average {
+        a: Box<PatternNode>,
+        b: Box<PatternNode>,
+    },
+    Multiply {
+        pattern: Box<PatternNode>,
+        modulator: Box<PatternNode>,
+    },
+}
+
+impl PatternNode {
+    /// Builds the pattern described by this tree.
+    pub fn build(&self) -> Box<dyn PatternGenerator> {
+        match self {
+            PatternNode::Pause { duration } => Box::new(Pause::new(*duration)),
+            PatternNode::Constant { level, duration } => Box::new(Constant::new(*level, *duration)),
+            PatternNode::Linear { from, to, duration } => {
+                Box::new(Linear::new(*from, *to, *duration))
+            }
+            PatternNode::Saw {
+                amplitude,
+                wavelength,
+            } => Box::new(SawWave::new(*amplitude, *wavelength)),
+            PatternNode::Triangle {
+                amplitude,
+                wavelength,
+            } => Box::new(TriangleWave::new(*amplitude, *wavelength)),
+            PatternNode::Square {
+                amplitude,
+                wavelength,
+            } => Box::new(SquareWave::new(*amplitude, *wavelength)),
+            PatternNode::Sine {
+                amplitude,
+                wavelength,
+            } => Box::new(SineWave::new(*amplitude, *wavelength)),
+            PatternNode::Pulse {
+                amplitude,
+                wavelength,
+                duty,
+            } => Box::new(Pulse::new(*amplitude, *wavelength, *duty)),
+            PatternNode::ScaleTime { pattern, scalar } => {
+                Box::new(pattern.build().scale_time(*scalar))
+            }
+            PatternNode::ScaleIntensity { pattern, scalar } => {
+                Box::new(pattern.build().scale_intensity(*scalar))
+            }
+            PatternNode::Offset { pattern, offset } => Box::new(pattern.build().offset(*offset)),
+            PatternNode::Clamp {
+                pattern,
+                floor,
+                ceiling,
+            } => Box::new(pattern.build().clamp(*floor, *ceiling)),
+            PatternNode::ClampValid { pattern } => Box::new(pattern.build().clamp_valid()),
+            PatternNode::ScaleValid { pattern } => Box::new(pattern.build().scale_valid()),
+            PatternNode::Shift {
+                pattern,
+                time_shift,
+            } => Box::new(pattern.build().shift(*time_shift)),
+            PatternNode::Repeat { pattern, count } => Box::new(pattern.build().repeat(*count)),
+            PatternNode::Forever { pattern } => Box::new(pattern.build().forever()),
+            PatternNode::Chain { first, then } => Box::new(first.build().chain(then.build())),
+            PatternNode::Crossfade {
+                first,
+                then,
+                overlap_duration,
+            } => Box::new(first.build().crossfade(then.build(), *overlap_duration)),
+            PatternNode::Sum { a, b } => Box::new(a.build().sum(b.build())),
+            PatternNode::Subtract { a, b } => Box::new(a.build().subtract(b.build())),
+            PatternNode::Average { a, b } => Box::new(a.build().average(b.build())),
+            PatternNode::Multiply { pattern, modulator } => {
+                Box::new(pattern.build().multiply(modulator.build()))
+            }
+        }
+    }
+}
+
+/// An error parsing a pattern expression, at the byte `offset` into the expression where it was found.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+impl Error for ParseError {}
+
+/// Parses a pattern expression into a `PatternNode`.
+///
+/// Expressions are a shape followed by any number of transformer methods, mirroring the Rust API, such as
+/// `sine(1.0, 0.5).repeat(4).scale_intensity(0.8)` or `constant(0.5, 2).chain(pause(1)).forever()`.
+/// All times are in seconds.
+///
+/// The shapes are `pause(duration)`, `constant(level, duration)`, `linear(from, to, duration)`,
+/// `saw`, `triangle`, `square` and `sine`, all taking `(amplitude, wavelength)`,
+/// and `pulse(amplitude, wavelength, duty)`.
+/// The methods are `scale_time`, `scale_intensity`, `offset`, `clamp(floor, ceiling)`, `clamp_valid()`,
+/// `scale_valid()`, `shift(time)`, `repeat(count)`, `forever()`, `chain(pattern)`,
+/// `crossfade(pattern, overlap)`, `sum`, `subtract`, `average` and `multiply`, taking the same arguments
+/// as the methods of `Pattern`.
+///
+/// Patterns can be nested as arguments up to `MAX_NESTING` deep.
+pub fn parse_pattern(input: &str) -> Result<PatternNode, ParseError> {
+    let mut parser = Parser {
+        input,
+        pos: 0,
+        depth: 0,
+    };
+    let node = parser.expression()?;
+    parser.skip_whitespace();
+    if parser.pos < input.len() {
+        return Err(parser.error("unexpected input after the pattern"));
+    }
+    Ok(node)
+}
+
+enum Arg {
+    Number(f64),
+    Pattern(PatternNode),
+}
+
+/// The arguments of a shape or method call, with the byte offset of each argument.
+struct Args {
+    name_offset: usize,
+    name: String,
+    items: Vec<(usize, Arg)>,
+}
+
+impl Args {
+    fn error(offset: usize, message: String) -> ParseError {
+        ParseError { offset, message }
+    }
+
+    fn expect(&self, count: usize) -> Result<(), ParseError> {
+        if self.items.len() == count {
+            Ok(())
+        } else {
+            Err(Self::error(
+                self.name_offset,
+                format!(
+                    "`{}` takes {count} argument{} but was given {}",
+                    self.name,
+                    if count == 1 { "" } else { "s" },
+                    self.items.len()
+                ),
+            ))
+        }
+    }
+
+    fn number(&self, index: usize) -> Result<f64, ParseError> {
+        match &self.items[index] {
+            (_, Arg::Number(value)) => Ok(*value),
+            (offset, Arg::Pattern(_)) => Err(Self::error(
+                *offset,
+                format!(
+                    "expected a number for argument {} of `{}`",
+                    index + 1,
+                    self.name
+                ),
+            )),
+        }
+    }
+
+    fn secs(&self, index: usize) -> Result<Duration, ParseError> {
+        let value = self.number(index)?;
+        Duration::try_from_secs_f64(value).map_err(|_| {
+            Self::error(
+                self.items[index].0,
+                format!("expected a non-negative number of seconds, found {value}"),
+            )
+        })
+    }
+
+    fn pattern(&mut self, index: usize) -> Result<Box<PatternNode>, ParseError> {
+        let (offset, arg) = &mut self.items[index];
+        // each argument is only taken once, so it can be moved out
+        match std::mem::replace(arg, Arg::Number(0.0)) {
+            Arg::Pattern(node) => Ok(Box::new(node)),
+            Arg::Number(_) => Err(Self::error(
+                *offset,
+                format!(
+                    "expected a pattern for argument {} of `{}`",
+                    index + 1,
+                    self.name
+                ),
+            )),
+        }
+    }
+}
+
+/// How deeply patterns can be nested as arguments of other patterns, so parsing can't overflow the stack.
+pub const MAX_NESTING: usize = 64;
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> ParseError {
+        ParseError {
+            offset: self.pos,
+            message: message.to_string(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += self.peek().map_or(0, char::len_utf8);
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn take_while(&mut self, accept: impl Fn(char) -> bool) -> &str {
+        let start = self.pos;
+        while self.peek().is_some_and(&accept) {
+            self.pos += self.peek().map_or(0, char::len_utf8);
+        }
+        &self.input[start..self.pos]
+    }
+
+    fn name(&mut self) -> Result<(usize, String), ParseError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+            self.pos = start;
+            return Err(self.error("expected a shape or method name"));
+        }
+        Ok((start, name.to_string()))
+    }
+
+    fn arguments(&mut self, name_offset: usize, name: String) -> Result<Args, ParseError> {
+        if !self.eat('(') {
+            return Err(self.error("expected `(`"));
+        }
+        let mut items = Vec::new();
+        if !self.eat(')') {
+            loop {
+                self.skip_whitespace();
+                let start = self.pos;
+                let arg = match self.peek() {
+                    Some(c) if c.is_ascii_digit() || c == '-' || c == '.' => {
+                        let number = self.take_while(|c| {
+                            c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E')
+                        });
+                        match number.parse() {
+                            Ok(value) => Arg::Number(value),
+                            Err(_) => {
+                                self.pos = start;
+                                return Err(self.error("invalid number"));
+                            }
+                        }
+                    }
+                    _ => {
+                        if self.depth == MAX_NESTING {
+                            return Err(self.error("patterns are nested too deeply"));
+                        }
+                        self.depth += 1;
+                        let node = self.expression()?;
+                        self.depth -= 1;
+                        Arg::Pattern(node)
+                    }
+                };
+                items.push((start, arg));
+                if self.eat(')') {
+                    break;
+                }
+                if !self.eat(',') {
+                    return Err(self.error("expected `,` or `)`"));
+                }
+            }
+        }
+        Ok(Args {
+            name_offset,
+            name,
+            items,
+        })
+    }
+
+    fn expression(&mut self) -> Result<PatternNode, ParseError> {
+        let (offset, name) = self.name()?;
+        let args = self.arguments(offset, name)?;
+        let mut node = shape(args)?;
+        while self.eat('.') {
+            let (offset, name) = self.name()?;
+            let args = self.arguments(offset, name)?;
+            node = method(node, args)?;
+        }
+        Ok(node)
+    }
+}
+
+fn shape(args: Args) -> Result<PatternNode, ParseError> {
+    let wave = |args: &Args| -> Result<(f64, Duration), ParseError> {
+        args.expect(2)?;
+        Ok((args.number(0)?, args.secs(1)?))
+    };
+    Ok(match args.name.as_str() {
+        "pause" => {
+            args.expect(1)?;
+            PatternNode::Pause {
+                duration: args.secs(0)?,
+            }
+        }
+        "constant" => {
+            args.expect(2)?;
+            PatternNode::Constant {
+                level: args.number(0)?,
+                duration: args.secs(1)?,
+            }
+        }
+        "linear" => {
+            args.expect(3)?;
+            PatternNode::Linear {
+                from: args.number(0)?,
+                to: args.number(1)?,
+                duration: args.secs(2)?,
+            }
+        }
+        "saw" => {
+            let (amplitude, wavelength) = wave(&args)?;
+            PatternNode::Saw {
+                amplitude,
+                wavelength,
+            }
+        }
+        "triangle" => {
+            let (amplitude, wavelength) = wave(&args)?;
+            PatternNode::Triangle {
+                amplitude,
+                wavelength,
+            }
+        }
+        "square" => {
+            let (amplitude, wavelength) = wave(&args)?;
+            PatternNode::Square {
+                amplitude,
+                wavelength,
+            }
+        }
+        "sine" => {
+            let (amplitude, wavelength) = wave(&args)?;
+            PatternNode::Sine {
+                amplitude,
+                wavelength,
+            }
+        }
+        "pulse" => {
+            args.expect(3)?;
+            PatternNode::Pulse {
+                amplitude: args.number(0)?,
+                wavelength: args.secs(1)?,
+                duty: args.number(2)?,
+            }
+        }
+        name => {
+            return Err(Args::error(
+                args.name_offset,
+                format!("unknown shape `{name}`"),
+            ))
+        }
+    })
+}
+
+fn method(node: PatternNode, mut args: Args) -> Result<PatternNode, ParseError> {
+    let pattern = Box::new(node);
+    Ok(match args.name.as_str() {
+        "scale_time" => {
+            args.expect(1)?;
+            PatternNode::ScaleTime {
+                pattern,
+                scalar: args.number(0)?,
+            }
+        }
+        "scale_intensity" => {
+            args.expect(1)?;
+            PatternNode::ScaleIntensity {
+                pattern,
+                scalar: args.number(0)?,
+            }
+        }
+        "offset" => {
+            args.expect(1)?;
+            PatternNode::Offset {
+                pattern,
+                offset: args.number(0)?,
+            }
+        }
+        "clamp" => {
+            args.expect(2)?;
+            PatternNode::Clamp {
+                pattern,
+                floor: args.number(0)?,
+                ceiling: args.number(1)?,
+            }
+        }
+        "clamp_valid" => {
+            args.expect(0)?;
+            PatternNode::ClampValid { pattern }
+        }
+        "scale_valid" => {
+            args.expect(0)?;
+            PatternNode::ScaleValid { pattern }
+        }
+        "shift" => {
+            args.expect(1)?;
+            PatternNode::Shift {
+                pattern,
+                time_shift: args.secs(0)?,
+            }
+        }
+        "repeat" => {
+            args.expect(1)?;
+            PatternNode::Repeat {
+                pattern,
+                count: args.number(0)?,
+            }
+        }
+        "forever" => {
+            args.expect(0)?;
+            PatternNode::Forever { pattern }
+        }
+        "chain" => {
+            args.expect(1)?;
+            PatternNode::Chain {
+                first: pattern,
+                then: args.pattern(0)?,
+            }
+        }
+        "crossfade" => {
+            args.expect(2)?;
+            PatternNode::Crossfade {
+                first: pattern,
+                then: args.pattern(0)?,
+                overlap_duration: args.secs(1)?,
+            }
+        }
+        "sum" => {
+            args.expect(1)?;
+            PatternNode::Sum {
+                a: pattern,
+                b: args.pattern(0)?,
+            }
+        }
+        "subtract" => {
+            args.expect(1)?;
+            PatternNode::Subtract {
+                a: pattern,
+                b: args.pattern(0)?,
+            }
+        }
+        "average" => {
+            args.expect(1)?;
+            PatternNode::Average {
+                a: pattern,
+                b: args.pattern(0)?,
+            }
+        }
+        "multiply" => {
+            args.expect(1)?;
+            PatternNode::Multiply {
+                pattern,
+                modulator: args.pattern(0)?,
+            }
+        }
+        name => {
+            return Err(Args::error(
+                args.name_offset,
+                format!("unknown method `{name}`"),
+            ))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern_trees_build_the_same_pattern() {
+        let node = PatternNode::Chain {
+            first: Box::new(PatternNode::Constant {
+                level: 0.5,
+                duration: Duration::from_secs(1),
+            }),
+            then: Box::new(PatternNode::Offset {
+                pattern: Box::new(PatternNode::Saw {
+                    amplitude: 0.6,
+                    wavelength: Duration::from_secs(1),
+                }),
+                offset: 0.2,
+            }),
+        };
+        let mut built = node.build();
+        let mut direct = Constant::new(0.5, Duration::from_secs(1))
+            .chain(SawWave::new(0.6, Duration::from_secs(1)).offset(0.2));
+        assert_eq!(built.duration(), direct.duration());
+        for tick in 0..100 {
+            let time = Duration::from_millis(tick * 20);
+            assert_eq!(built.sample(time), direct.sample(time));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn pattern_trees_round_trip_through_json() {
+        let node = PatternNode::Repeat {
+            pattern: Box::new(PatternNode::ScaleIntensity {
+                pattern: Box::new(PatternNode::Sine {
+                    amplitude: 1.0,
+                    wavelength: Duration::from_millis(500),
+                }),
+                scalar: 0.8,
+            }),
+            count: 4.0,
+        };
+        let json = serde_json::to_string(&node).unwrap();
+        let loaded: PatternNode = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, node);
+        let (mut original, mut loaded) = (node.build(), loaded.build());
+        assert_eq!(loaded.duration(), Duration::from_secs(2));
+        for tick in 0..100 {
+            let time = Duration::from_millis(tick * 20);
+            assert_eq!(loaded.sample(time), original.sample(time));
+        }
+    }
+
+    #[test]
+    fn expressions_parse_into_pattern_trees() {
+        let parsed = parse_pattern("sine(1.0, 0.5).repeat(4).scale_intensity(0.8)").unwrap();
+        let expected = PatternNode::ScaleIntensity {
+            pattern: Box::new(PatternNode::Repeat {
+                pattern: Box::new(PatternNode::Sine {
+                    amplitude: 1.0,
+                    wavelength: Duration::from_millis(500),
+                }),
+                count: 4.0,
+            }),
+            scalar: 0.8,
+        };
+        assert_eq!(parsed, expected);
+
+        let parsed = parse_pattern(" constant(0.5, 2) . chain( pause(1) ) ").unwrap();
+        let expected = PatternNode::Chain {
+            first: Box::new(PatternNode::Constant {
+                level: 0.5,
+                duration: Duration::from_secs(2),
+            }),
+            then: Box::new(PatternNode::Pause {
+                duration: Duration::from_secs(1),
+            }),
+        };
+        assert_eq!(parsed, expected);
+        assert_eq!(parsed.build().duration(), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn parse_errors_report_where_they_happened() {
+        let error = parse_pattern("sine(1, 1).chain(wobble(1, 1))").unwrap_err();
+        assert_eq!(error.offset, 17);
+        assert_eq!(error.message, "unknown shape `wobble`");
+
+        let error = parse_pattern("sine(1, 1).spin()").unwrap_err();
+        assert_eq!(error.offset, 11);
+        assert_eq!(error.message, "unknown method `spin`");
+
+        let error = parse_pattern("linear(0, 1)").unwrap_err();
+        assert_eq!(error.offset, 0);
+        assert_eq!(error.message, "`linear` takes 3 arguments but was given 2");
+
+        let error = parse_pattern("pause(1).repeat(2, 3)").unwrap_err();
+        assert_eq!(error.offset, 9);
+        assert_eq!(error.message, "`repeat` takes 1 argument but was given 2");
+
+        assert_eq!(parse_pattern("pause(-1)").unwrap_err().offset, 6);
+        assert_eq!(parse_pattern("pause(1) pause(1)").unwrap_err().offset, 9);
+        assert_eq!(parse_pattern("pause(1").unwrap_err().offset, 7);
+    }
+
+    #[test]
+    fn parse_rejects_deeply_nested_patterns() {
+        let nested = |depth| {
+            let mut input = "pause(1)".to_string();
+            for _ in 0..depth {
+                input = format!("pause(1).chain({input})");
+            }
+            input
+        };
+        assert!(parse_pattern(&nested(MAX_NESTING)).is_ok());
+        let error = parse_pattern(&nested(MAX_NESTING + 1)).unwrap_err();
+        assert_eq!(error.message, "patterns are nested too deeply");
+        // at the start of the argument one level too deep
+        assert_eq!(error.offset, (MAX_NESTING + 1) * "pause(1).chain(".len());
+        // far deeper than the stack could take is still an error rather than a crash
+        assert!(parse_pattern(&"pause(1).chain(".repeat(1_000_000)).is_err());
+    }
+}
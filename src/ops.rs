@@ -0,0 +1,197 @@
+//! Operator overloads for composing patterns.
+//!
+//! Rust doesn't allow implementing operators for every type implementing `Pattern`,
+//! so they are implemented for each pattern type in the crate instead.
+//! Custom patterns can still use the named methods of `Pattern`.
+
+use std::ops::{Add, Mul, Sub};
+
+use crate::funscript::Funscript;
+use crate::random::*;
+use crate::reader::*;
+use crate::shapes::*;
+use crate::transformers::*;
+use crate::{CustomPattern, Pattern};
+
+macro_rules! impl_pattern_ops {
+    ($($ty:ident $(<$($generic:ident),+>)?),* $(,)?) => {$(
+        /// `a + b` is the same as `a.sum(b)`.
+        impl<$($($generic: Pattern,)+)? Rhs: Pattern> Add<Rhs> for $ty $(<$($generic),+>)? {
+            type Output = Sum<Self, Rhs>;
+
+            fn add(self, rhs: Rhs) -> Self::Output {
+                self.sum(rhs)
+            }
+        }
+
+        /// `a - b` is the same as `a.subtract(b)`.
+        impl<$($($generic: Pattern,)+)? Rhs: Pattern> Sub<Rhs> for $ty $(<$($generic),+>)? {
+            type Output = Subtract<Self, Rhs>;
+
+            fn sub(self, rhs: Rhs) -> Self::Output {
+                self.subtract(rhs)
+            }
+        }
+
+        /// `a + x` is the same as `a.offset(x)`.
+        impl$(<$($generic: Pattern),+>)? Add<f64> for $ty $(<$($generic),+>)? {
+            type Output = Offset<Self>;
+
+            fn add(self, rhs: f64) -> Self::Output {
+                self.offset(rhs)
+            }
+        }
+
+        /// `a - x` is the same as `a.offset(-x)`.
+        impl$(<$($generic: Pattern),+>)? Sub<f64> for $ty $(<$($generic),+>)? {
+            type Output = Offset<Self>;
+
+            fn sub(self, rhs: f64) -> Self::Output {
+                self.offset(-rhs)
+            }
+        }
+
+        /// `a * x` is the same as `a.scale_intensity(x)`.
+        impl$(<$($generic: Pattern),+>)? Mul<f64> for $ty $(<$($generic),+>)? {
+            type Output = ScaleIntensity<Self>;
+
+            fn mul(self, rhs: f64) -> Self::Output {
+                self.scale_intensity(rhs)
+            }
+        }
+
+        /// `a * b` is the same as `a.multiply(b)`.
+        impl<$($($generic: Pattern,)+)? Rhs: Pattern> Mul<Rhs> for $ty $(<$($generic),+>)? {
+            type Output = AmplitudeModulator<Self, Rhs>;
+
+            fn mul(self, rhs: Rhs) -> Self::Output {
+                self.multiply(rhs)
+            }
+        }
+    )*};
+}
+
+impl_pattern_ops!(
+    CustomPattern,
+    // shapes
+    Pause,
+    Constant,
+    Linear,
+    SawWave,
+    TriangleWave,
+    SquareWave,
+    Pulse,
+    SineWave,
+    TableSine,
+    FourierSynth,
+    Arpeggio,
+    DampedSine,
+    Trapezoid,
+    Adsr,
+    Edging,
+    WavePacket,
+    ExpDecay,
+    Staircase,
+    TwoRate,
+    Countdown,
+    StepSequence,
+    GateSequence,
+    // random
+    Random,
+    RandomEvery,
+    RandomWalk,
+    PinkNoise,
+    Chaos,
+    Rumble,
+    SmoothNoise,
+    // reader
+    ReaderPattern,
+    ImageScan,
+    Funscript,
+    CsvEnvelope,
+    // transformers
+    ScaleTime<P>,
+    ScaleIntensity<P>,
+    Offset<P>,
+    Sum<P, Q>,
+    Subtract<P, Q>,
+    Average<P, Q>,
+    Min<P, Q>,
+    Max<P, Q>,
+    Clamp<P>,
+    ValidScale<P>,
+    Shift<P>,
+    Repeat<P>,
+    Forever<P>,
+    Chain<P, Q>,
+    Crossfade<P, Q>,
+    AmplitudeModulator<P, M>,
+    FrequencyModulator<P, M>,
+    AccelLimit<P>,
+    DynamicTremolo<C, D>,
+    AutoTail<P>,
+    Crush<P>,
+    Quantize<P>,
+    RoundRobin,
+    MovingAverage<P>,
+    CrossSynth<A, B>,
+    Reflect<P>,
+    Invert<P>,
+    RisingAccent<P, A>,
+    EnforceRest<P>,
+    MarkovSwitch,
+    Intro<I, B>,
+    Duck<B, T>,
+    DeadZone<P>,
+    Expand<P>,
+    Fill<P, F>,
+    EaseTime<P>,
+    Ease<P>,
+    Chorus<P>,
+    ToggleOnCross<C>,
+    LookAhead<P>,
+    Limiter<P>,
+    Sequence,
+    PunchIn<P>,
+    PeakFollower<P>,
+    Phases,
+    Widen<L, R>,
+    Sanitize<P>,
+    Accelerando<P>,
+    FreezeOnHold<P>,
+    BeatRepeat<P>,
+    Baked,
+    RealtimeSync<P>,
+);
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::shapes::{SineWave, SquareWave};
+    use crate::Pattern;
+
+    fn assert_same(mut a: impl Pattern, mut b: impl Pattern) {
+        for tick in 0..100 {
+            let time = Duration::from_millis(tick * 20);
+            assert_eq!(a.sample(time), b.sample(time));
+        }
+    }
+
+    #[test]
+    fn pattern_operators_match_named_methods() {
+        let sine = SineWave::new(0.6, Duration::from_secs(1));
+        let square = SquareWave::new(0.3, Duration::from_millis(300));
+        assert_same(sine + square, sine.sum(square));
+        assert_same(sine - square, sine.subtract(square));
+        assert_same(sine * square, sine.multiply(square));
+    }
+
+    #[test]
+    fn scalar_operators_match_named_methods() {
+        let sine = SineWave::new(0.6, Duration::from_secs(1));
+        assert_same(sine * 0.5, sine.scale_intensity(0.5));
+        assert_same(sine + 0.2, sine.offset(0.2));
+        assert_same(sine - 0.2, sine.offset(-0.2));
+    }
+}
@@ -0,0 +1,268 @@
+use std::{error::Error, fmt, time::Duration};
+
+use serde::Deserialize;
+
+use crate::node::PatternNode;
+use crate::PatternGenerator;
+
+/// A built-in preset that can be referenced by a playlist entry.
+///
+/// Waves are repeated to fill the duration of their entry, wavelengths are in seconds.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "preset", rename_all = "snake_case")]
+pub enum Preset {
+    Pause,
+    Constant {
+        level: f64,
+    },
+    Linear {
+        from: f64,
+        to: f64,
+    },
+    Saw {
+        amplitude: f64,
+        wavelength: f64,
+    },
+    Triangle {
+        amplitude: f64,
+        wavelength: f64,
+    },
+    Square {
+        amplitude: f64,
+        wavelength: f64,
+    },
+    Sine {
+        amplitude: f64,
+        wavelength: f64,
+    },
+    Pulse {
+        amplitude: f64,
+        wavelength: f64,
+        duty: f64,
+    },
+}
+
+/// A single entry of a playlist, playing a preset for `duration` seconds.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Entry {
+    #[serde(flatten)]
+    pub preset: Preset,
+    pub duration: f64,
+}
+
+/// An error in a playlist, with the index of the entry that caused it if there is one.
+#[derive(Debug)]
+pub struct PlaylistError {
+    pub entry: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for PlaylistError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.entry {
+            Some(entry) => write!(f, "invalid playlist entry {entry}: {}", self.message),
+            None => write!(f, "invalid playlist: {}", self.message),
+        }
+    }
+}
+
+impl Error for PlaylistError {}
+
+impl Entry {
+    /// Lowers the entry to the `PatternNode` it plays, with waves repeated to fill its duration.
+    pub fn to_node(&self, index: usize) -> Result<PatternNode, PlaylistError> {
+        let error = |message: &str| PlaylistError {
+            entry: Some(index),
+            message: message.to_string(),
+        };
+        let seconds = |secs: f64, name: &str| {
+            Duration::try_from_secs_f64(secs)
+                .map_err(|_| error(&format!("{name} must be a non-negative number of seconds")))
+        };
+        let duration = seconds(self.duration, "duration")?;
+        let wave = |wavelength: f64, shape: &dyn Fn(Duration) -> PatternNode| {
+            let wavelength = seconds(wavelength, "wavelength")?;
+            if wavelength.is_zero() {
+                return Err(error("wavelength must not be zero"));
+            }
+            Ok(PatternNode::Repeat {
+                pattern: Box::new(shape(wavelength)),
+                count: duration.as_secs_f64() / wavelength.as_secs_f64(),
+            })
+        };
+        match self.preset {
+            Preset::Pause => Ok(PatternNode::Pause { duration }),
+            Preset::Constant { level } => Ok(PatternNode::Constant { level, duration }),
+            Preset::Linear { from, to } => Ok(PatternNode::Linear { from, to, duration }),
+            Preset::Saw {
+                amplitude,
+                wavelength,
+            } => wave(wavelength, &|wavelength| PatternNode::Saw {
+                amplitude,
+                wavelength,
+            }),
+            Preset::Triangle {
+                amplitude,
+                wavelength,
+            } => wave(wavelength, &|wavelength| PatternNode::Triangle {
+                amplitude,
+                wavelength,
+            }),
+            Preset::Square {
+                amplitude,
+                wavelength,
+            } => wave(wavelength, &|wavelength| PatternNode::Square {
+                amplitude,
+                wavelength,
+            }),
+            Preset::Sine {
+                amplitude,
+                wavelength,
+            } => wave(wavelength, &|wavelength| PatternNode::Sine {
+                amplitude,
+                wavelength,
+            }),
+            Preset::Pulse {
+                amplitude,
+                wavelength,
+                duty,
+            } => wave(wavelength, &|wavelength| PatternNode::Pulse {
+                amplitude,
+                wavelength,
+                duty,
+            }),
+        }
+    }
+}
+
+/// Chains `nodes` one after another as a balanced tree, so each sample only passes through
+/// a logarithmic number of chains however long the playlist is.
+fn chain_balanced(mut nodes: Vec<PatternNode>) -> Option<PatternNode> {
+    if nodes.len() <= 1 {
+        return nodes.pop();
+    }
+    let then = nodes.split_off(nodes.len() / 2);
+    Some(PatternNode::Chain {
+        first: Box::new(chain_balanced(nodes)?),
+        then: Box::new(chain_balanced(then)?),
+    })
+}
+
+/// Lowers a playlist to a single `PatternNode` playing each entry in order.
+pub fn to_node(entries: &[Entry]) -> Result<PatternNode, PlaylistError> {
+    let nodes = entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| entry.to_node(index))
+        .collect::<Result<Vec<_>, _>>()?;
+    chain_balanced(nodes).ok_or_else(|| PlaylistError {
+        entry: None,
+        message: "playlist has no entries".to_string(),
+    })
+}
+
+/// Builds a single pattern playing each entry of a playlist in order.
+pub fn from_entries(entries: &[Entry]) -> Result<Box<dyn PatternGenerator>, PlaylistError> {
+    Ok(to_node(entries)?.build())
+}
+
+/// Parses a JSON playlist, a list of entries such as
+/// `[{"preset": "sine", "amplitude": 1.0, "wavelength": 0.5, "duration": 4.0}, {"preset": "pause", "duration": 1.0}]`,
+/// and builds a single pattern playing each entry in order.
+pub fn from_json(json: &str) -> Result<Box<dyn PatternGenerator>, PlaylistError> {
+    let values: Vec<serde_json::Value> = serde_json::from_str(json).map_err(|e| PlaylistError {
+        entry: None,
+        message: e.to_string(),
+    })?;
+    let entries = values
+        .into_iter()
+        .enumerate()
+        .map(|(index, value)| {
+            Entry::deserialize(value).map_err(|e| PlaylistError {
+                entry: Some(index),
+                message: e.to_string(),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    from_entries(&entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAYLIST: &str = r#"[
+        {"preset": "constant", "level": 0.4, "duration": 1.0},
+        {"preset": "pause", "duration": 0.5},
+        {"preset": "linear", "from": 0.0, "to": 1.0, "duration": 2.0}
+    ]"#;
+
+    #[test]
+    fn json_playlist_plays_entries_in_order() {
+        let mut pattern = from_json(PLAYLIST).unwrap();
+        assert_eq!(pattern.duration(), Duration::from_millis(3500));
+        assert_eq!(pattern.sample(Duration::from_millis(900)), 0.4);
+        assert_eq!(pattern.sample(Duration::from_millis(1100)), 0.0);
+        assert!((pattern.sample(Duration::from_millis(1500)) - 0.0).abs() < 1e-9);
+        assert!((pattern.sample(Duration::from_millis(2500)) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_entry() {
+        let unknown = from_json(
+            r#"[{"preset": "pause", "duration": 1.0}, {"preset": "wobble", "duration": 1.0}]"#,
+        );
+        assert_eq!(unknown.err().unwrap().entry, Some(1));
+        let zero_wavelength = from_json(
+            r#"[{"preset": "sine", "amplitude": 1.0, "wavelength": 0.0, "duration": 1.0}]"#,
+        );
+        assert_eq!(zero_wavelength.err().unwrap().entry, Some(0));
+        let empty = from_json("[]");
+        assert_eq!(empty.err().unwrap().entry, None);
+    }
+
+    #[test]
+    fn waves_are_lowered_to_repeats_filling_their_entry() {
+        let entry = Entry {
+            preset: Preset::Sine {
+                amplitude: 1.0,
+                wavelength: 0.5,
+            },
+            duration: 2.0,
+        };
+        let expected = PatternNode::Repeat {
+            pattern: Box::new(PatternNode::Sine {
+                amplitude: 1.0,
+                wavelength: Duration::from_millis(500),
+            }),
+            count: 4.0,
+        };
+        assert_eq!(entry.to_node(0).unwrap(), expected);
+    }
+
+    #[test]
+    fn long_playlists_chain_as_a_balanced_tree() {
+        fn depth(node: &PatternNode) -> usize {
+            match node {
+                PatternNode::Chain { first, then } => 1 + depth(first).max(depth(then)),
+                _ => 0,
+            }
+        }
+        let entries: Vec<Entry> = (0..1000)
+            .map(|index| Entry {
+                preset: Preset::Constant {
+                    level: index as f64 / 1000.0,
+                },
+                duration: 1.0,
+            })
+            .collect();
+        let node = to_node(&entries).unwrap();
+        assert_eq!(depth(&node), 10);
+        let mut pattern = node.build();
+        assert_eq!(pattern.duration(), Duration::from_secs(1000));
+        for index in [0, 1, 499, 500, 999] {
+            let time = Duration::from_millis(index * 1000 + 500);
+            assert_eq!(pattern.sample(time), index as f64 / 1000.0);
+        }
+    }
+}
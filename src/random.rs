@@ -3,29 +3,61 @@ use std::{
     time::{Duration, Instant},
 };
 
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
 use crate::PatternGenerator;
 
+/// A random number generator for step `step` of a pattern seeded with `seed`,
+/// so patterns can pick the same value for a step every time it's sampled.
+fn step_rng(seed: u64, step: u64) -> StdRng {
+    StdRng::seed_from_u64(seed ^ step.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+}
+
 /// Generates a random value between the given range every tick.
+///
+/// Patterns made with `with_seed` produce the same sequence of values every time they're played.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Random {
     pub range: Range<f64>,
     pub duration: Duration,
+    seed: Option<u64>,
+    rng: StdRng,
 }
 
 impl Random {
     pub fn new(range: Range<f64>, duration: Duration) -> Self {
-        Random { range, duration }
+        Random {
+            range,
+            duration,
+            seed: None,
+            rng: StdRng::from_os_rng(),
+        }
+    }
+
+    pub fn with_seed(range: Range<f64>, duration: Duration, seed: u64) -> Self {
+        Random {
+            range,
+            duration,
+            seed: Some(seed),
+            rng: StdRng::seed_from_u64(seed),
+        }
     }
 }
 
 impl PatternGenerator for Random {
     fn sample(&mut self, _time: Duration) -> f64 {
-        rand::random_range(self.range.clone())
+        self.rng.random_range(self.range.clone())
     }
 
     fn duration(&self) -> Duration {
         self.duration
     }
+
+    fn reset(&mut self) {
+        if let Some(seed) = self.seed {
+            self.rng = StdRng::seed_from_u64(seed);
+        }
+    }
 }
 
 /// Generates a random value between the given range every `interval` seconds.
@@ -115,3 +147,319 @@ impl PatternGenerator for RandomWalk {
         self.state = 0.0;
     }
 }
+
+const PINK_NOISE_ROWS: usize = 8;
+
+/// Generates pink (1/f) noise between the given range every tick.
+///
+/// Uses the Voss-McCartney algorithm: several random rows are summed, with each row
+/// updated half as often as the one before it, so slow changes dominate over fast ones.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PinkNoise {
+    pub range: Range<f64>,
+    pub duration: Duration,
+    seed: u64,
+    rng: StdRng,
+    rows: [f64; PINK_NOISE_ROWS],
+    counter: u32,
+}
+
+impl PinkNoise {
+    pub fn new(range: Range<f64>, duration: Duration, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let rows = std::array::from_fn(|_| rng.random());
+        PinkNoise {
+            range,
+            duration,
+            seed,
+            rng,
+            rows,
+            counter: 0,
+        }
+    }
+}
+
+impl PatternGenerator for PinkNoise {
+    fn sample(&mut self, _time: Duration) -> f64 {
+        self.counter = self.counter.wrapping_add(1);
+        // row `n` is updated every 2^n ticks
+        let row = (self.counter.trailing_zeros() as usize).min(PINK_NOISE_ROWS - 1);
+        self.rows[row] = self.rng.random();
+        let noise = self.rows.iter().sum::<f64>() / PINK_NOISE_ROWS as f64;
+        self.range.start + noise * (self.range.end - self.range.start)
+    }
+
+    fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    fn reset(&mut self) {
+        *self = PinkNoise::new(self.range.clone(), self.duration, self.seed);
+    }
+}
+
+/// Generates a chaotic but deterministic sequence from the logistic map `x = r * x * (1 - x)`.
+///
+/// The map is advanced every `every`, and the output is held between updates. Sampling a time means advancing
+/// the map through every step before it, so `every` is at least `MIN_CHAOS_STEP`, and shorter steps are raised to it.
+/// Values of `r` between roughly 3.57 and 4.0 give chaotic output, lower values settle into cycles.
+/// The output stays between 0.0 and 1.0 for `r` between 0.0 and 4.0.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chaos {
+    pub r: f64,
+    pub every: Duration,
+    pub duration: Duration,
+    initial: f64,
+    step: u64,
+    state: f64,
+}
+
+/// The shortest step of `Chaos`, so catching up to a time an hour in takes a few million steps at most.
+pub const MIN_CHAOS_STEP: Duration = Duration::from_millis(1);
+
+impl Chaos {
+    pub fn new(r: f64, every: Duration, duration: Duration, seed: u64) -> Self {
+        // avoid the fixed points at 0.0 and 1.0
+        let initial = StdRng::seed_from_u64(seed).random_range(0.01..0.99);
+        Chaos {
+            r,
+            every: every.max(MIN_CHAOS_STEP),
+            duration,
+            initial,
+            step: 0,
+            state: initial,
+        }
+    }
+}
+
+impl PatternGenerator for Chaos {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let every = self.every.max(MIN_CHAOS_STEP);
+        let step = (time.as_secs_f64() / every.as_secs_f64()) as u64;
+        if step < self.step {
+            self.reset();
+        }
+        while self.step < step {
+            self.state = self.r * self.state * (1.0 - self.state);
+            self.step += 1;
+        }
+        self.state
+    }
+
+    fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    fn reset(&mut self) {
+        self.step = 0;
+        self.state = self.initial;
+    }
+}
+
+/// Generates a rumble, a steady `base` level with fast, low amplitude noise of up to `depth` on top.
+///
+/// A new noise value is picked `noise_rate` times per second. The noise is seeded from the time,
+/// so sampling the same time always gives the same value and the output stays within `base ± depth`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rumble {
+    pub base: f64,
+    pub depth: f64,
+    pub noise_rate: f64,
+    pub duration: Duration,
+    seed: u64,
+}
+
+impl Rumble {
+    pub fn new(base: f64, depth: f64, noise_rate: f64, duration: Duration, seed: u64) -> Self {
+        Rumble {
+            base,
+            depth,
+            noise_rate,
+            duration,
+            seed,
+        }
+    }
+}
+
+impl PatternGenerator for Rumble {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let step = (time.as_secs_f64() * self.noise_rate) as u64;
+        self.base + self.depth * step_rng(self.seed, step).random_range(-1.0..=1.0)
+    }
+
+    fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+/// Generates smooth value noise between 0.0 and `amplitude`, that plays forever without repeating.
+///
+/// A random value is picked every `feature_size`, and the output eases between them, so it wanders
+/// organically instead of jumping every tick like `Random`. The values are seeded from the time,
+/// so sampling the same time with the same seed always gives the same value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SmoothNoise {
+    pub amplitude: f64,
+    pub feature_size: Duration,
+    seed: u64,
+}
+
+impl SmoothNoise {
+    pub fn new(amplitude: f64, feature_size: Duration, seed: u64) -> Self {
+        SmoothNoise {
+            amplitude,
+            feature_size,
+            seed,
+        }
+    }
+}
+
+impl PatternGenerator for SmoothNoise {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let value = |step: u64| step_rng(self.seed, step).random::<f64>();
+        if self.feature_size.is_zero() {
+            return self.amplitude * value(0);
+        }
+        let position = time.as_secs_f64() / self.feature_size.as_secs_f64();
+        let step = position as u64;
+        let t = position.fract();
+        // smoothstep, so the slope is continuous at each picked value
+        let t = t * t * (3.0 - 2.0 * t);
+        self.amplitude * (value(step) + (value(step + 1) - value(step)) * t)
+    }
+
+    fn duration(&self) -> Duration {
+        Duration::MAX
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// Average absolute change between consecutive samples, which is lower for noise dominated by slow changes.
+    fn roughness<P: PatternGenerator>(pattern: &mut P, count: u64) -> f64 {
+        let samples: Vec<f64> = (0..count)
+            .map(|tick| pattern.sample(Duration::from_millis(tick * 10)))
+            .collect();
+        samples.windows(2).map(|w| (w[1] - w[0]).abs()).sum::<f64>() / (count - 1) as f64
+    }
+
+    #[test]
+    fn pink_noise_stays_in_range() {
+        let mut noise = PinkNoise::new(0.2..0.6, Duration::from_secs(10), 7);
+        for tick in 0..1000 {
+            let value = noise.sample(Duration::from_millis(tick * 10));
+            assert!((0.2..0.6).contains(&value), "{value}");
+        }
+    }
+
+    #[test]
+    fn pink_noise_is_smoother_than_white_noise() {
+        let mut pink = PinkNoise::new(0.0..1.0, Duration::from_secs(10), 7);
+        let mut white = Random::with_seed(0.0..1.0, Duration::from_secs(10), 7);
+        assert!(roughness(&mut pink, 1000) < roughness(&mut white, 1000) / 2.0);
+    }
+
+    #[test]
+    fn chaos_steps_are_at_least_the_minimum() {
+        let mut chaos = Chaos::new(3.9, Duration::ZERO, Duration::from_secs(5), 3);
+        assert_eq!(chaos.every, MIN_CHAOS_STEP);
+        let mut twin = Chaos::new(3.9, MIN_CHAOS_STEP, Duration::from_secs(5), 3);
+        // a step set below the minimum after creating is raised to it as well
+        chaos.every = Duration::from_nanos(1);
+        for ms in [0, 1, 2, 1000, 4999] {
+            let time = Duration::from_millis(ms);
+            assert_eq!(chaos.sample(time), twin.sample(time));
+        }
+    }
+
+    #[test]
+    fn chaos_is_deterministic_and_in_range() {
+        let every = Duration::from_millis(50);
+        let mut a = Chaos::new(3.9, every, Duration::from_secs(5), 3);
+        let mut b = Chaos::new(3.9, every, Duration::from_secs(5), 3);
+        let values: Vec<f64> = (0..100)
+            .map(|tick| a.sample(Duration::from_millis(tick * 50)))
+            .collect();
+        for (tick, value) in values.iter().enumerate() {
+            assert_eq!(*value, b.sample(Duration::from_millis(tick as u64 * 50)));
+            assert!((0.0..=1.0).contains(value));
+        }
+        // held between updates
+        assert_eq!(
+            a.sample(Duration::from_millis(4960)),
+            a.sample(Duration::from_millis(4990))
+        );
+    }
+
+    #[test]
+    fn rumble_varies_around_its_base() {
+        let mut rumble = Rumble::new(0.5, 0.1, 50.0, Duration::from_secs(10), 3);
+        for tick in 0..1000 {
+            let value = rumble.sample(Duration::from_millis(tick * 10));
+            assert!((0.4..=0.6).contains(&value), "{value}");
+        }
+        assert!(roughness(&mut rumble, 1000) > 0.01);
+        // the noise is fixed for each time
+        let time = Duration::from_millis(1234);
+        assert_eq!(rumble.sample(time), rumble.sample(time));
+    }
+
+    #[test]
+    fn smooth_noise_is_deterministic_bounded_and_smooth() {
+        let feature_size = Duration::from_millis(250);
+        let mut noise = SmoothNoise::new(0.7, feature_size, 42);
+        let mut twin = SmoothNoise::new(0.7, feature_size, 42);
+        let mut other = SmoothNoise::new(0.7, feature_size, 43);
+        // smoothstep is at most 1.5 times as steep as a straight line between two values
+        let bound = 1.5 * 0.7 * 0.005 / feature_size.as_secs_f64() + 1e-9;
+        let mut last = noise.sample(Duration::ZERO);
+        let mut differs = false;
+        for tick in 1..2000 {
+            let time = Duration::from_millis(tick * 5);
+            let value = noise.sample(time);
+            assert_eq!(value, twin.sample(time));
+            differs |= value != other.sample(time);
+            assert!((0.0..=0.7).contains(&value));
+            assert!(
+                (value - last).abs() <= bound,
+                "{last} to {value} at {time:?}"
+            );
+            last = value;
+        }
+        assert!(differs);
+        // sampling out of order gives the same values
+        assert_eq!(
+            noise.sample(Duration::from_millis(40)),
+            twin.sample(Duration::from_millis(40))
+        );
+        let earlier = noise.sample(Duration::from_millis(1234));
+        noise.sample(Duration::from_secs(7));
+        assert_eq!(noise.sample(Duration::from_millis(1234)), earlier);
+        // the noise never repeats, so it has no loop to report
+        assert_eq!(noise.period(), Duration::MAX);
+    }
+
+    #[test]
+    fn seeded_random_replays_the_same_sequence() {
+        let mut random = Random::with_seed(0.2..0.9, Duration::from_secs(1), 1234);
+        let mut twin = Random::with_seed(0.2..0.9, Duration::from_secs(1), 1234);
+        let samples: Vec<f64> = (0..100)
+            .map(|tick| random.sample(Duration::from_millis(tick * 10)))
+            .collect();
+        for (tick, value) in samples.iter().enumerate() {
+            assert_eq!(*value, twin.sample(Duration::from_millis(tick as u64 * 10)));
+            assert!((0.2..0.9).contains(value));
+        }
+        let mut other = Random::with_seed(0.2..0.9, Duration::from_secs(1), 4321);
+        assert!(samples
+            .iter()
+            .any(|value| *value != other.sample(Duration::ZERO)));
+        // resetting starts the sequence over
+        random.reset();
+        assert_eq!(random.sample(Duration::ZERO), samples[0]);
+    }
+}
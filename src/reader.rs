@@ -0,0 +1,399 @@
+use std::{
+    collections::VecDeque,
+    fs,
+    io::{self, BufRead, BufReader, Read},
+    path::Path,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use crate::funscript::interpolate;
+use crate::PatternGenerator;
+
+/// What a `ReaderPattern` does once its input has ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EofBehavior {
+    /// Keep returning the last value read until the pattern's duration runs out.
+    Hold,
+    /// End the pattern once all values read have been played.
+    End,
+}
+
+#[derive(Debug, Default)]
+struct ReaderState {
+    pending: VecDeque<(Duration, f64)>,
+    finished: bool,
+}
+
+/// Plays intensity values read line by line from a reader, such as stdin in a shell pipeline.
+///
+/// Each line is either a bare value, which is played as soon as it is read,
+/// or a `time,value` pair with the time in seconds, which is played once the pattern reaches that time.
+/// Lines that can't be parsed are skipped.
+///
+/// The reader is consumed on a background thread, so sampling never blocks on input.
+#[derive(Debug)]
+pub struct ReaderPattern {
+    pub duration: Duration,
+    pub on_eof: EofBehavior,
+    state: Arc<Mutex<ReaderState>>,
+    value: f64,
+    ended_at: Option<Duration>,
+}
+
+impl ReaderPattern {
+    pub fn new<R: 'static + Read + Send>(
+        reader: R,
+        duration: Duration,
+        on_eof: EofBehavior,
+    ) -> Self {
+        let state = Arc::new(Mutex::new(ReaderState::default()));
+        let thread_state = state.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(reader).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if let Some(point) = parse_line(&line) {
+                    thread_state.lock().unwrap().pending.push_back(point);
+                }
+            }
+            thread_state.lock().unwrap().finished = true;
+        });
+        ReaderPattern {
+            duration,
+            on_eof,
+            state,
+            value: 0.0,
+            ended_at: None,
+        }
+    }
+
+    /// Creates a pattern that reads values from stdin.
+    pub fn stdin(duration: Duration, on_eof: EofBehavior) -> Self {
+        Self::new(io::stdin(), duration, on_eof)
+    }
+}
+
+fn parse_line(line: &str) -> Option<(Duration, f64)> {
+    match line.split_once(',') {
+        Some((time, value)) => {
+            let time = time.trim().parse::<f64>().ok()?;
+            let value = value.trim().parse().ok()?;
+            Some((Duration::try_from_secs_f64(time).ok()?, value))
+        }
+        None => Some((Duration::ZERO, line.trim().parse().ok()?)),
+    }
+}
+
+impl PatternGenerator for ReaderPattern {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let mut state = self.state.lock().unwrap();
+        while let Some(&(at, value)) = state.pending.front() {
+            if at > time {
+                break;
+            }
+            self.value = value;
+            state.pending.pop_front();
+        }
+        if state.finished && state.pending.is_empty() && self.on_eof == EofBehavior::End {
+            self.ended_at.get_or_insert(time);
+        }
+        self.value
+    }
+
+    fn duration(&self) -> Duration {
+        self.ended_at
+            .map_or(self.duration, |ended_at| ended_at.min(self.duration))
+    }
+}
+
+/// Scans a grayscale image from left to right, playing the average brightness of each column as intensity.
+///
+/// The columns are spread evenly over `duration`, with black columns at 0.0 and white columns at 1.0.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageScan {
+    columns: Vec<f64>,
+    duration: Duration,
+}
+
+impl ImageScan {
+    /// Creates a scan from 8 bit grayscale pixels, stored row by row. Pixels past the last full row are
+    /// ignored, so an image without a full row plays as black.
+    pub fn from_luma(width: usize, pixels: &[u8], duration: Duration) -> Self {
+        let values: Vec<f64> = pixels.iter().map(|p| *p as f64 / 255.0).collect();
+        Self::from_values(width, &values, duration)
+    }
+
+    /// Loads a scan from a PGM (portable graymap) image file, in either the binary or plain format.
+    pub fn open(path: impl AsRef<Path>, duration: Duration) -> io::Result<Self> {
+        let (width, values) = parse_pgm(&fs::read(path)?)?;
+        Ok(Self::from_values(width, &values, duration))
+    }
+
+    fn from_values(width: usize, values: &[f64], duration: Duration) -> Self {
+        let height = values.len().checked_div(width).unwrap_or(0);
+        let columns = (0..width)
+            .map(|x| (0..height).map(|y| values[y * width + x]).sum::<f64>() / height.max(1) as f64)
+            .collect();
+        ImageScan { columns, duration }
+    }
+}
+
+fn invalid_pgm(message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid PGM image: {message}"),
+    )
+}
+
+/// Parses a PGM image into its width and its pixels scaled to 0.0 to 1.0.
+fn parse_pgm(data: &[u8]) -> io::Result<(usize, Vec<f64>)> {
+    let mut pos = 0;
+    let mut header = Vec::new();
+    while header.len() < 4 {
+        match data.get(pos) {
+            None => return Err(invalid_pgm("truncated header")),
+            Some(b'#') => {
+                while data.get(pos).is_some_and(|b| *b != b'\n') {
+                    pos += 1;
+                }
+            }
+            Some(b) if b.is_ascii_whitespace() => pos += 1,
+            Some(_) => {
+                let start = pos;
+                while data.get(pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+                    pos += 1;
+                }
+                header.push(String::from_utf8_lossy(&data[start..pos]).into_owned());
+            }
+        }
+    }
+    let number = |token: &str| {
+        token
+            .parse::<usize>()
+            .map_err(|_| invalid_pgm("header values must be positive integers"))
+    };
+    let (width, height, max) = (
+        number(&header[1])?,
+        number(&header[2])?,
+        number(&header[3])?,
+    );
+    if max == 0 {
+        return Err(invalid_pgm("maximum value must not be 0"));
+    }
+    let count = width
+        .checked_mul(height)
+        .ok_or_else(|| invalid_pgm("image dimensions are too large"))?;
+    let pixels: Vec<usize> = match header[0].as_str() {
+        "P5" => {
+            // a single whitespace character separates the header from the raster
+            let raster = data.get(pos + 1..).unwrap_or_default();
+            if max < 256 {
+                raster.iter().take(count).map(|b| *b as usize).collect()
+            } else {
+                raster
+                    .chunks_exact(2)
+                    .take(count)
+                    .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+                    .collect()
+            }
+        }
+        "P2" => String::from_utf8_lossy(&data[pos..])
+            .split_ascii_whitespace()
+            .take(count)
+            .map(number)
+            .collect::<io::Result<_>>()?,
+        _ => return Err(invalid_pgm("only P2 and P5 images are supported")),
+    };
+    if pixels.len() < count {
+        return Err(invalid_pgm("truncated pixel data"));
+    }
+    Ok((
+        width,
+        pixels.iter().map(|p| *p as f64 / max as f64).collect(),
+    ))
+}
+
+impl PatternGenerator for ImageScan {
+    fn sample(&mut self, time: Duration) -> f64 {
+        if self.columns.is_empty() {
+            return 0.0;
+        }
+        let progress = time.as_secs_f64() / self.duration.as_secs_f64();
+        let column = ((progress * self.columns.len() as f64) as usize).min(self.columns.len() - 1);
+        self.columns[column]
+    }
+
+    fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+/// Plays an envelope loaded from a two column CSV of `time_seconds,intensity` rows, such as one exported
+/// from a spreadsheet, linearly interpolating between rows.
+///
+/// Rows are sorted by time when loaded, and the first and last intensities are held before the first row
+/// and after the last. The pattern ends at the last row's time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CsvEnvelope {
+    points: Vec<(Duration, f64)>,
+}
+
+impl CsvEnvelope {
+    /// Loads an envelope from CSV read from `reader`.
+    ///
+    /// Blank lines are skipped, and so is a header row if the first row doesn't parse as numbers.
+    /// Any other row that isn't a non-negative time and an intensity is an error, including a negative time
+    /// in the first row.
+    pub fn from_reader<R: Read>(reader: R) -> io::Result<Self> {
+        let mut points = Vec::new();
+        let mut first = true;
+        for (number, line) in BufReader::new(reader).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let invalid = |problem: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "invalid CSV envelope row {}, {problem}: {line:?}",
+                        number + 1
+                    ),
+                )
+            };
+            let numbers = line.split_once(',').and_then(|(time, value)| {
+                Some((
+                    time.trim().parse::<f64>().ok()?,
+                    value.trim().parse::<f64>().ok()?,
+                ))
+            });
+            match numbers {
+                Some((time, value)) => {
+                    let time = Duration::try_from_secs_f64(time)
+                        .map_err(|_| invalid("times must be non-negative seconds"))?;
+                    points.push((time, value));
+                }
+                // a header row is the only row allowed not to be numbers
+                None if first => {}
+                None => return Err(invalid("expected a time and an intensity")),
+            }
+            first = false;
+        }
+        points.sort_by_key(|(at, _)| *at);
+        Ok(CsvEnvelope { points })
+    }
+
+    /// Loads an envelope from a CSV file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_reader(fs::File::open(path)?)
+    }
+}
+
+impl PatternGenerator for CsvEnvelope {
+    fn sample(&mut self, time: Duration) -> f64 {
+        interpolate(&self.points, time)
+    }
+
+    fn duration(&self) -> Duration {
+        self.points.last().map_or(Duration::ZERO, |(at, _)| *at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_pgm_reads_plain_images() {
+        let (width, pixels) = parse_pgm(b"P2\n# comment\n2 1\n4\n0 4\n").unwrap();
+        assert_eq!(width, 2);
+        assert_eq!(pixels, vec![0.0, 1.0]);
+    }
+
+    #[test]
+    fn parse_pgm_rejects_overflowing_dimensions() {
+        let header = format!("P5 {} 2 255 ", usize::MAX);
+        let error = parse_pgm(header.as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn parse_pgm_rejects_truncated_pixels() {
+        assert!(parse_pgm(b"P2 2 2 255 0 1 2").is_err());
+    }
+
+    #[test]
+    fn image_scan_plays_columns_left_to_right() {
+        let mut scan = ImageScan::from_luma(2, &[0, 255], Duration::from_secs(2));
+        assert_eq!(scan.duration(), Duration::from_secs(2));
+        assert_eq!(scan.sample(Duration::ZERO), 0.0);
+        assert_eq!(scan.sample(Duration::from_millis(500)), 0.0);
+        assert_eq!(scan.sample(Duration::from_millis(1500)), 1.0);
+        assert_eq!(scan.sample(Duration::from_secs(2)), 1.0);
+    }
+
+    #[test]
+    fn image_scan_without_a_full_row_is_black() {
+        let mut scan = ImageScan::from_luma(4, &[255, 255], Duration::from_secs(1));
+        for ms in [0, 400, 999] {
+            assert_eq!(scan.sample(Duration::from_millis(ms)), 0.0);
+        }
+    }
+
+    #[test]
+    fn csv_envelope_skips_a_text_header() {
+        let mut envelope =
+            CsvEnvelope::from_reader(&b"time,intensity\n0,0.0\n2,1.0\n"[..]).unwrap();
+        assert_eq!(envelope.duration(), Duration::from_secs(2));
+        assert!((envelope.sample(Duration::from_secs(1)) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn csv_envelope_reads_numeric_first_rows() {
+        let envelope = CsvEnvelope::from_reader(&b".5,0.2\n1,0.4\n"[..]).unwrap();
+        assert_eq!(envelope.points.len(), 2);
+    }
+
+    #[test]
+    fn csv_envelope_rejects_negative_times() {
+        assert!(CsvEnvelope::from_reader(&b"-1,0.5\n1,0.4\n"[..]).is_err());
+        assert!(CsvEnvelope::from_reader(&b"0,0.5\n-1,0.4\n"[..]).is_err());
+    }
+
+    #[test]
+    fn csv_envelope_rejects_text_after_the_first_row() {
+        assert!(CsvEnvelope::from_reader(&b"0,0.5\nsoon,0.4\n"[..]).is_err());
+    }
+
+    /// Waits for the background thread of `pattern` to read all of its input.
+    fn wait_for_input(pattern: &ReaderPattern) {
+        while !pattern.state.lock().unwrap().finished {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn reader_pattern_plays_timed_values_in_order() {
+        let input = io::Cursor::new("0,0.2\n1,0.5\nnot a value\n2,0.9\n");
+        let mut pattern = ReaderPattern::new(input, Duration::from_secs(10), EofBehavior::Hold);
+        wait_for_input(&pattern);
+        let sampled: Vec<f64> = [0, 500, 1000, 2500, 9000]
+            .iter()
+            .map(|ms| pattern.sample(Duration::from_millis(*ms)))
+            .collect();
+        assert_eq!(sampled, vec![0.2, 0.2, 0.5, 0.9, 0.9]);
+        assert_eq!(pattern.duration(), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn reader_pattern_ends_with_its_input() {
+        let input = io::Cursor::new("0.3\n0.6\n");
+        let mut pattern = ReaderPattern::new(input, Duration::from_secs(10), EofBehavior::End);
+        wait_for_input(&pattern);
+        assert_eq!(pattern.sample(Duration::from_secs(1)), 0.6);
+        assert_eq!(pattern.duration(), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,20 @@
+//! Serializes `Duration`s as a number of seconds, for use with `#[serde(with = "crate::secs")]`.
+
+use std::time::Duration;
+
+use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+pub(crate) fn serialize<S: Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Duration, D::Error> {
+    let secs = f64::deserialize(deserializer)?;
+    Duration::try_from_secs_f64(secs)
+        .map_err(|_| D::Error::custom("durations must be a non-negative number of seconds"))
+}
@@ -1,10 +1,19 @@
-use std::{f64::consts::PI, time::Duration};
+use std::{
+    f64::consts::{PI, TAU},
+    sync::OnceLock,
+    time::Duration,
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 use crate::PatternGenerator;
 
 /// Generates a zero value for a given duration.
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Pause {
+    #[cfg_attr(feature = "serde", serde(with = "crate::secs"))]
     duration: Duration,
 }
 
@@ -26,8 +35,10 @@ impl PatternGenerator for Pause {
 
 /// Generates a constant value for a given duration.
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Constant {
     level: f64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::secs"))]
     duration: Duration,
 }
 
@@ -49,9 +60,11 @@ impl PatternGenerator for Constant {
 
 /// Generates a linear value between two points for a given duration.
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Linear {
     from: f64,
     to: f64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::secs"))]
     duration: Duration,
 }
 
@@ -76,8 +89,10 @@ impl PatternGenerator for Linear {
 /// Waves are generated as single pulses with a given wavelength.
 /// To play several cycles of the wave in sequence, use the `repeat` method.
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SawWave {
     amplitude: f64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::secs"))]
     wavelength: Duration,
 }
 
@@ -105,8 +120,10 @@ impl PatternGenerator for SawWave {
 /// Waves are generated as single pulses with a given wavelength.
 /// To play several cycles of the wave in sequence, use the `repeat` method.
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TriangleWave {
     amplitude: f64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::secs"))]
     wavelength: Duration,
 }
 
@@ -141,8 +158,10 @@ impl PatternGenerator for TriangleWave {
 /// Waves are generated as single pulses with a given wavelength.
 /// To play several cycles of the wave in sequence, use the `repeat` method.
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SquareWave {
     amplitude: f64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::secs"))]
     wavelength: Duration,
 }
 
@@ -170,13 +189,55 @@ impl PatternGenerator for SquareWave {
     }
 }
 
+/// Generates a pulse wave between 0 and an amplitude, staying at the amplitude for the first `duty`
+/// fraction of each cycle and at 0 for the rest.
+///
+/// A `duty` of 0.5 is the same as a `SquareWave`, `duty` is clamped between 0.0 and 1.0.
+/// Waves are generated as single pulses with a given wavelength.
+/// To play several cycles of the wave in sequence, use the `repeat` method.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Pulse {
+    amplitude: f64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::secs"))]
+    wavelength: Duration,
+    duty: f64,
+}
+
+impl Pulse {
+    pub fn new(amplitude: f64, wavelength: Duration, duty: f64) -> Self {
+        Pulse {
+            amplitude,
+            wavelength,
+            duty: duty.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl PatternGenerator for Pulse {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let wavelength = self.wavelength.as_secs_f64();
+        if time.as_secs_f64() % wavelength < self.duty * wavelength {
+            self.amplitude
+        } else {
+            0.0
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        self.wavelength
+    }
+}
+
 /// Generates a Sine wave between 0 and an amplitude for a given duration.
 ///
 /// Waves are generated as single pulses with a given wavelength.
 /// To play several cycles of the wave in sequence, use the `repeat` method.
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SineWave {
     amplitude: f64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::secs"))]
     wavelength: Duration,
 }
 
@@ -194,8 +255,7 @@ impl PatternGenerator for SineWave {
         // sine value between 0 and `amplitude` based on a wavelength of `wavelength` starting at 0
         (self.amplitude / 2.0)
             * f64::cos(
-                2.0 * PI
-                    * (1.0 / self.wavelength.as_secs_f64())
+                TAU * (1.0 / self.wavelength.as_secs_f64())
                     * (time.as_secs_f64() + self.wavelength.as_secs_f64() / 2.0),
             )
             + self.amplitude / 2.0
@@ -205,3 +265,1008 @@ impl PatternGenerator for SineWave {
         self.wavelength
     }
 }
+
+const SINE_TABLE_SIZE: usize = 1024;
+
+/// One cycle of a cosine, with the first value repeated at the end so interpolation never wraps.
+fn sine_table() -> &'static [f64; SINE_TABLE_SIZE + 1] {
+    static TABLE: OnceLock<[f64; SINE_TABLE_SIZE + 1]> = OnceLock::new();
+    TABLE.get_or_init(|| std::array::from_fn(|i| f64::cos(TAU * i as f64 / SINE_TABLE_SIZE as f64)))
+}
+
+/// Generates the same wave as `SineWave`, looking it up in a shared precomputed table instead of
+/// computing a cosine on every sample.
+///
+/// Values are linearly interpolated between table entries, keeping them within about 1e-5 of `SineWave`.
+/// The table is built once, the first time any `TableSine` is sampled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TableSine {
+    amplitude: f64,
+    wavelength: Duration,
+}
+
+impl TableSine {
+    pub fn new(amplitude: f64, wavelength: Duration) -> Self {
+        TableSine {
+            amplitude,
+            wavelength,
+        }
+    }
+}
+
+impl PatternGenerator for TableSine {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let cycle = (time.as_secs_f64() / self.wavelength.as_secs_f64()).rem_euclid(1.0);
+        let position = cycle * SINE_TABLE_SIZE as f64;
+        let index = (position as usize).min(SINE_TABLE_SIZE - 1);
+        let fraction = position - index as f64;
+        let table = sine_table();
+        let cosine = table[index] + (table[index + 1] - table[index]) * fraction;
+        // same phase as `SineWave`, starting from 0 at the start of each cycle
+        (self.amplitude / 2.0) * (1.0 - cosine)
+    }
+
+    fn duration(&self) -> Duration {
+        self.wavelength
+    }
+}
+
+/// Generates a wave from the amplitudes and phases of its harmonics.
+///
+/// Each `(amplitude, phase)` pair is a cosine at a multiple of the fundamental frequency,
+/// starting with the fundamental itself, and the output is their sum.
+/// Phases are in radians.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FourierSynth {
+    wavelength: Duration,
+    harmonics: Vec<(f64, f64)>,
+}
+
+impl FourierSynth {
+    pub fn new(wavelength: Duration, harmonics: Vec<(f64, f64)>) -> Self {
+        FourierSynth {
+            wavelength,
+            harmonics,
+        }
+    }
+}
+
+impl PatternGenerator for FourierSynth {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let phase = 2.0 * PI * time.as_secs_f64() / self.wavelength.as_secs_f64();
+        self.harmonics
+            .iter()
+            .zip(1..)
+            .map(|(&(amplitude, offset), harmonic)| {
+                amplitude * f64::cos(harmonic as f64 * phase + offset)
+            })
+            .sum()
+    }
+
+    fn duration(&self) -> Duration {
+        self.wavelength
+    }
+}
+
+/// Generates a Sine wave between 0 and an amplitude that steps through a list of frequencies.
+///
+/// Each frequency, in Hz, is held for `step` before moving on to the next,
+/// and the wave restarts from 0 at each step.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Arpeggio {
+    frequencies: Vec<f64>,
+    step: Duration,
+    amplitude: f64,
+}
+
+impl Arpeggio {
+    pub fn new(frequencies: Vec<f64>, step: Duration, amplitude: f64) -> Self {
+        Arpeggio {
+            frequencies,
+            step,
+            amplitude,
+        }
+    }
+}
+
+impl PatternGenerator for Arpeggio {
+    fn sample(&mut self, time: Duration) -> f64 {
+        if self.frequencies.is_empty() || self.step.is_zero() {
+            return 0.0;
+        }
+        let step = self.step.as_secs_f64();
+        let index = ((time.as_secs_f64() / step) as usize).min(self.frequencies.len() - 1);
+        let frequency = self.frequencies[index];
+        let step_time = time.as_secs_f64() - index as f64 * step;
+        (self.amplitude / 2.0) * f64::cos(2.0 * PI * frequency * step_time + PI)
+            + self.amplitude / 2.0
+    }
+
+    fn duration(&self) -> Duration {
+        u32::try_from(self.frequencies.len())
+            .ok()
+            .and_then(|len| self.step.checked_mul(len))
+            .unwrap_or(Duration::MAX)
+    }
+}
+
+/// Generates a Sine wave between 0 and an amplitude that decays exponentially, like a struck resonator ringing out.
+///
+/// The amplitude of the oscillation falls by a factor of `1/e` every `decay`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DampedSine {
+    amplitude: f64,
+    wavelength: Duration,
+    decay: Duration,
+    duration: Duration,
+}
+
+impl DampedSine {
+    pub fn new(amplitude: f64, wavelength: Duration, decay: Duration, duration: Duration) -> Self {
+        DampedSine {
+            amplitude,
+            wavelength,
+            decay,
+            duration,
+        }
+    }
+}
+
+impl PatternGenerator for DampedSine {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let time = time.as_secs_f64();
+        self.amplitude
+            * f64::exp(-time / self.decay.as_secs_f64())
+            * (1.0 - f64::cos(2.0 * PI * time / self.wavelength.as_secs_f64()))
+            / 2.0
+    }
+
+    fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+/// Generates a trapezoid envelope, ramping from 0 up to a peak, holding it, then ramping back down to 0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Trapezoid {
+    attack: Duration,
+    hold: Duration,
+    release: Duration,
+    peak: f64,
+}
+
+impl Trapezoid {
+    pub fn new(attack: Duration, hold: Duration, release: Duration, peak: f64) -> Self {
+        Trapezoid {
+            attack,
+            hold,
+            release,
+            peak,
+        }
+    }
+}
+
+impl PatternGenerator for Trapezoid {
+    fn sample(&mut self, time: Duration) -> f64 {
+        if time < self.attack {
+            self.peak * time.as_secs_f64() / self.attack.as_secs_f64()
+        } else if time <= self.attack + self.hold {
+            self.peak
+        } else if time < self.duration() {
+            let released = (time - self.attack - self.hold).as_secs_f64();
+            self.peak * (1.0 - released / self.release.as_secs_f64())
+        } else {
+            0.0
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        self.attack + self.hold + self.release
+    }
+}
+
+/// Generates an ADSR envelope, ramping from 0 up to `peak` over `attack`, falling to `sustain_level` over `decay`,
+/// holding `sustain_level` for `sustain`, then falling back to 0 over `release`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Adsr {
+    attack: Duration,
+    decay: Duration,
+    sustain_level: f64,
+    sustain: Duration,
+    release: Duration,
+    peak: f64,
+}
+
+impl Adsr {
+    pub fn new(
+        attack: Duration,
+        decay: Duration,
+        sustain_level: f64,
+        sustain: Duration,
+        release: Duration,
+        peak: f64,
+    ) -> Self {
+        Adsr {
+            attack,
+            decay,
+            sustain_level,
+            sustain,
+            release,
+            peak,
+        }
+    }
+}
+
+impl PatternGenerator for Adsr {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let lerp = |from: f64, to: f64, elapsed: Duration, length: Duration| {
+            from + (to - from) * elapsed.as_secs_f64() / length.as_secs_f64()
+        };
+        let decayed = self.attack + self.decay;
+        let sustained = decayed + self.sustain;
+        if time < self.attack {
+            lerp(0.0, self.peak, time, self.attack)
+        } else if time < decayed {
+            lerp(
+                self.peak,
+                self.sustain_level,
+                time - self.attack,
+                self.decay,
+            )
+        } else if time <= sustained {
+            self.sustain_level
+        } else if time < self.duration() {
+            lerp(self.sustain_level, 0.0, time - sustained, self.release)
+        } else {
+            0.0
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        self.attack + self.decay + self.sustain + self.release
+    }
+}
+
+/// Generates an edging cycle, repeatedly ramping up to a high plateau, holding near it with small
+/// oscillations, then dropping back down to 0.
+///
+/// Each of the `plateau_count` cycles ramps linearly from 0 to `plateau_level` over `ramp`, then oscillates
+/// around `plateau_level` by up to `oscillation_depth`, `oscillation_hz` times per second, for `plateau`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Edging {
+    plateau_level: f64,
+    oscillation_depth: f64,
+    oscillation_hz: f64,
+    plateau: Duration,
+    ramp: Duration,
+    plateau_count: u32,
+}
+
+impl Edging {
+    pub fn new(
+        plateau_level: f64,
+        oscillation_depth: f64,
+        oscillation_hz: f64,
+        plateau: Duration,
+        ramp: Duration,
+        plateau_count: u32,
+    ) -> Self {
+        Edging {
+            plateau_level,
+            oscillation_depth,
+            oscillation_hz,
+            plateau,
+            ramp,
+            plateau_count,
+        }
+    }
+}
+
+impl PatternGenerator for Edging {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let cycle = self.ramp.saturating_add(self.plateau).as_secs_f64();
+        if cycle == 0.0 || time >= self.duration() {
+            return 0.0;
+        }
+        let offset = time.as_secs_f64() % cycle;
+        let ramp = self.ramp.as_secs_f64();
+        if offset < ramp {
+            self.plateau_level * offset / ramp
+        } else {
+            self.plateau_level
+                + self.oscillation_depth
+                    * f64::sin(2.0 * PI * self.oscillation_hz * (offset - ramp))
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        self.ramp
+            .saturating_add(self.plateau)
+            .checked_mul(self.plateau_count)
+            .unwrap_or(Duration::MAX)
+    }
+}
+
+/// Generates a wave packet, a short burst of a sine carrier under a Gaussian envelope.
+///
+/// The carrier oscillates `carrier_hz` times per second between 0 and the envelope, and is aligned so
+/// the packet peaks at `amplitude` exactly at `center`. The envelope falls off on both sides of `center`,
+/// `width` is its standard deviation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WavePacket {
+    carrier_hz: f64,
+    center: Duration,
+    width: Duration,
+    amplitude: f64,
+    duration: Duration,
+}
+
+impl WavePacket {
+    pub fn new(
+        carrier_hz: f64,
+        center: Duration,
+        width: Duration,
+        amplitude: f64,
+        duration: Duration,
+    ) -> Self {
+        WavePacket {
+            carrier_hz,
+            center,
+            width,
+            amplitude,
+            duration,
+        }
+    }
+}
+
+impl PatternGenerator for WavePacket {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let offset = time.as_secs_f64() - self.center.as_secs_f64();
+        let width = self.width.as_secs_f64();
+        if width == 0.0 {
+            return if offset == 0.0 { self.amplitude } else { 0.0 };
+        }
+        let envelope = f64::exp(-offset * offset / (2.0 * width * width));
+        let carrier = (1.0 + f64::cos(TAU * self.carrier_hz * offset)) / 2.0;
+        self.amplitude * envelope * carrier
+    }
+
+    fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+/// Generates an exponential decay from `start` towards 0, for a more natural fade than a linear ramp.
+///
+/// The value falls to about 37% of `start` after each `time_constant`, and starts at exactly `start`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExpDecay {
+    start: f64,
+    time_constant: Duration,
+    duration: Duration,
+}
+
+impl ExpDecay {
+    pub fn new(start: f64, time_constant: Duration, duration: Duration) -> Self {
+        ExpDecay {
+            start,
+            time_constant,
+            duration,
+        }
+    }
+}
+
+impl PatternGenerator for ExpDecay {
+    fn sample(&mut self, time: Duration) -> f64 {
+        if time.is_zero() {
+            return self.start;
+        }
+        if self.time_constant.is_zero() {
+            return 0.0;
+        }
+        self.start * (-time.as_secs_f64() / self.time_constant.as_secs_f64()).exp()
+    }
+
+    fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+/// Generates a staircase, stepping evenly between `floor` and `ceiling` in `steps` equal length steps.
+///
+/// Staircases made with `new` rise from `floor` on the first step to `ceiling` on the last,
+/// staircases made with `descending` step down from `ceiling` to `floor` instead.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Staircase {
+    floor: f64,
+    ceiling: f64,
+    steps: u32,
+    duration: Duration,
+    descending: bool,
+}
+
+impl Staircase {
+    pub fn new(floor: f64, ceiling: f64, steps: u32, duration: Duration) -> Self {
+        Staircase {
+            floor,
+            ceiling,
+            steps,
+            duration,
+            descending: false,
+        }
+    }
+
+    /// Creates a staircase stepping down from `ceiling` on the first step to `floor` on the last.
+    pub fn descending(floor: f64, ceiling: f64, steps: u32, duration: Duration) -> Self {
+        Staircase {
+            descending: true,
+            ..Self::new(floor, ceiling, steps, duration)
+        }
+    }
+}
+
+impl PatternGenerator for Staircase {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let (from, to) = if self.descending {
+            (self.ceiling, self.floor)
+        } else {
+            (self.floor, self.ceiling)
+        };
+        if self.steps <= 1 || self.duration.is_zero() {
+            return from;
+        }
+        let progress = time.as_secs_f64() / self.duration.as_secs_f64();
+        let step = ((progress * self.steps as f64) as u32).min(self.steps - 1);
+        if step == self.steps - 1 {
+            // land on the last level exactly rather than through rounding
+            return to;
+        }
+        from + (to - from) * step as f64 / (self.steps - 1) as f64
+    }
+
+    fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+/// Generates a wave on a wave, a slow sine carrier whose level is shaped by a fast sine modulator.
+///
+/// The carrier swells between 0 and `slow_amplitude` once every `slow_wavelength`, and the modulator dips it
+/// by up to `fast_depth` of its level once every `fast_wavelength`, so the output stays between 0 and
+/// `slow_amplitude`. This is the same as modulating a repeated `SineWave` with another, packaged as one shape.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TwoRate {
+    slow: SineWave,
+    fast: SineWave,
+    fast_depth: f64,
+    duration: Duration,
+}
+
+impl TwoRate {
+    pub fn new(
+        slow_amplitude: f64,
+        slow_wavelength: Duration,
+        fast_depth: f64,
+        fast_wavelength: Duration,
+        duration: Duration,
+    ) -> Self {
+        TwoRate {
+            slow: SineWave::new(slow_amplitude, slow_wavelength),
+            fast: SineWave::new(1.0, fast_wavelength),
+            fast_depth: fast_depth.clamp(0.0, 1.0),
+            duration,
+        }
+    }
+}
+
+impl PatternGenerator for TwoRate {
+    fn sample(&mut self, time: Duration) -> f64 {
+        // sine waves are periodic, so they can be sampled past their wavelength
+        let modulation = 1.0 - self.fast_depth + self.fast_depth * self.fast.sample(time);
+        self.slow.sample(time) * modulation
+    }
+
+    fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+/// Generates an accelerating metronome for a countdown, pulsing at full intensity with a rate
+/// that ramps from `start_rate_hz` to `end_rate_hz` pulses per second over `duration`.
+///
+/// Each pulse is on for the first half of its beat, so pulses get shorter as well as closer together
+/// as the countdown runs out. Use `scale_intensity` to change the level of the pulses.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Countdown {
+    start_rate_hz: f64,
+    end_rate_hz: f64,
+    duration: Duration,
+}
+
+impl Countdown {
+    pub fn new(duration: Duration, start_rate_hz: f64, end_rate_hz: f64) -> Self {
+        Countdown {
+            start_rate_hz,
+            end_rate_hz,
+            duration,
+        }
+    }
+}
+
+impl PatternGenerator for Countdown {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let t = time.min(self.duration).as_secs_f64();
+        let length = self.duration.as_secs_f64();
+        // number of beats so far, the integral of the linearly ramping rate
+        let mut beats = self.start_rate_hz * t;
+        if length > 0.0 {
+            beats += (self.end_rate_hz - self.start_rate_hz) * t * t / (2.0 * length);
+        }
+        if beats.fract() < 0.5 {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+/// Generates a step sequence, a pulse at each step's level every `step`, with the off-beats swung by `groove`.
+///
+/// Each pulse lasts half a step. Every second step, the off-beats, is delayed by `groove` times the step
+/// duration for a looser, more human feel, while the on-beats stay on the grid. `groove` is clamped
+/// between 0.0, playing straight, and 0.5, where off-beat pulses end just as the next step starts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StepSequence {
+    levels: Vec<f64>,
+    step: Duration,
+    groove: f64,
+}
+
+impl StepSequence {
+    pub fn new(levels: Vec<f64>, step: Duration, groove: f64) -> Self {
+        StepSequence {
+            levels,
+            step,
+            groove: groove.clamp(0.0, 0.5),
+        }
+    }
+}
+
+impl PatternGenerator for StepSequence {
+    fn sample(&mut self, time: Duration) -> f64 {
+        if self.levels.is_empty() || self.step.is_zero() {
+            return 0.0;
+        }
+        let step = self.step.as_secs_f64();
+        let index = ((time.as_secs_f64() / step) as usize).min(self.levels.len() - 1);
+        let delay = if index % 2 == 1 { self.groove } else { 0.0 };
+        let progress = time.as_secs_f64() / step - index as f64 - delay;
+        if (0.0..0.5).contains(&progress) {
+            self.levels[index]
+        } else {
+            0.0
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        u32::try_from(self.levels.len())
+            .ok()
+            .and_then(|len| self.step.checked_mul(len))
+            .unwrap_or(Duration::MAX)
+    }
+}
+
+/// Generates a gate sequence, a train of on and off segments of any length, at `amplitude` while on and 0 while off.
+///
+/// Each segment is an `(on, length)` pair, played in order. This is a more flexible pulse train than a
+/// `SquareWave` for timings authored by hand.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GateSequence {
+    segments: Vec<(bool, Duration)>,
+    amplitude: f64,
+}
+
+impl GateSequence {
+    pub fn new(segments: Vec<(bool, Duration)>, amplitude: f64) -> Self {
+        GateSequence {
+            segments,
+            amplitude,
+        }
+    }
+}
+
+impl PatternGenerator for GateSequence {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let mut end = Duration::ZERO;
+        for &(on, length) in &self.segments {
+            end += length;
+            if time < end {
+                return if on { self.amplitude } else { 0.0 };
+            }
+        }
+        0.0
+    }
+
+    fn duration(&self) -> Duration {
+        self.segments.iter().map(|&(_, length)| length).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+    use std::time::Duration;
+
+    use super::*;
+
+    fn times(count: u64, step_ms: u64) -> impl Iterator<Item = Duration> {
+        (0..count).map(move |tick| Duration::from_millis(tick * step_ms))
+    }
+
+    #[test]
+    fn fourier_synth_fundamental_is_a_cosine() {
+        let mut synth = FourierSynth::new(Duration::from_secs(1), vec![(0.5, 0.0)]);
+        assert_eq!(synth.duration(), Duration::from_secs(1));
+        for time in times(100, 10) {
+            let expected = 0.5 * (2.0 * PI * time.as_secs_f64()).cos();
+            assert!((synth.sample(time) - expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn fourier_synth_sums_harmonics() {
+        let mut synth =
+            FourierSynth::new(Duration::from_secs(2), vec![(0.5, 0.0), (0.25, PI / 2.0)]);
+        for time in times(100, 20) {
+            let phase = PI * time.as_secs_f64();
+            let expected = 0.5 * phase.cos() + 0.25 * (2.0 * phase + PI / 2.0).cos();
+            assert!((synth.sample(time) - expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn arpeggio_changes_frequency_at_each_step() {
+        let mut arpeggio = Arpeggio::new(vec![1.0, 4.0], Duration::from_secs(1), 1.0);
+        assert_eq!(arpeggio.duration(), Duration::from_secs(2));
+        // a whole cycle of 1 Hz peaks once in the middle of its step, and 4 Hz at every eighth
+        assert!((arpeggio.sample(Duration::from_millis(500)) - 1.0).abs() < 1e-9);
+        assert!((arpeggio.sample(Duration::from_millis(1125)) - 1.0).abs() < 1e-9);
+        assert!(arpeggio.sample(Duration::from_millis(1250)).abs() < 1e-9);
+        assert!(
+            (arpeggio.sample(Duration::from_millis(1500))
+                - arpeggio.sample(Duration::from_millis(1000)))
+            .abs()
+                < 1e-9
+        );
+    }
+
+    #[test]
+    fn arpeggio_duration_saturates() {
+        let arpeggio = Arpeggio::new(vec![1.0, 2.0, 3.0], Duration::MAX / 2, 1.0);
+        assert_eq!(arpeggio.duration(), Duration::MAX);
+    }
+
+    #[test]
+    fn damped_sine_decays_by_one_over_e_per_decay() {
+        let mut ring = DampedSine::new(
+            0.8,
+            Duration::from_millis(200),
+            Duration::from_secs(1),
+            Duration::from_secs(3),
+        );
+        // peaks of the oscillation are half a wavelength into each cycle
+        let first = ring.sample(Duration::from_millis(100));
+        let later = ring.sample(Duration::from_millis(1100));
+        assert!((later / first - (-1.0f64).exp()).abs() < 1e-9);
+        assert_eq!(ring.sample(Duration::ZERO), 0.0);
+        assert_eq!(ring.duration(), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn trapezoid_ramps_holds_and_releases() {
+        let mut envelope = Trapezoid::new(
+            Duration::from_secs(1),
+            Duration::from_secs(2),
+            Duration::from_secs(1),
+            0.8,
+        );
+        assert_eq!(envelope.duration(), Duration::from_secs(4));
+        assert_eq!(envelope.sample(Duration::ZERO), 0.0);
+        assert!((envelope.sample(Duration::from_millis(500)) - 0.4).abs() < 1e-9);
+        assert!(times(21, 100)
+            .map(|time| envelope.sample(time + Duration::from_secs(1)))
+            .all(|sample| sample == 0.8));
+        assert!((envelope.sample(Duration::from_millis(3500)) - 0.4).abs() < 1e-9);
+        assert_eq!(envelope.sample(Duration::from_secs(4)), 0.0);
+    }
+
+    #[test]
+    fn edging_oscillates_at_the_plateau_without_exceeding_it() {
+        let mut edging = Edging::new(
+            0.7,
+            0.1,
+            1.0,
+            Duration::from_secs(3),
+            Duration::from_secs(2),
+            3,
+        );
+        assert_eq!(edging.duration(), Duration::from_secs(15));
+        let samples: Vec<f64> = times(1500, 10).map(|time| edging.sample(time)).collect();
+        assert!(samples.iter().all(|sample| *sample <= 0.8 + 1e-9));
+        // each plateau reaches above and below its level
+        for plateau in samples.chunks(500).map(|cycle| &cycle[200..]) {
+            assert!(plateau.iter().any(|sample| *sample > 0.79));
+            assert!(plateau.iter().any(|sample| *sample < 0.61));
+        }
+        assert!((samples[100] - 0.35).abs() < 1e-9);
+        assert_eq!(edging.sample(Duration::from_secs(15)), 0.0);
+    }
+
+    #[test]
+    fn edging_oscillates_at_its_rate() {
+        let ramp = Duration::from_secs(1);
+        let mut edging = Edging::new(0.5, 0.2, 4.0, Duration::from_secs(2), ramp, 1);
+        // a quarter of an oscillation into the plateau is its peak, half is back at the level
+        let peak = edging.sample(ramp + Duration::from_micros(62_500));
+        assert!((peak - 0.7).abs() < 1e-9, "{peak}");
+        let level = edging.sample(ramp + Duration::from_millis(125));
+        assert!((level - 0.5).abs() < 1e-9, "{level}");
+    }
+
+    #[test]
+    fn edging_duration_saturates() {
+        let long = Duration::MAX / 2;
+        let mut edging = Edging::new(0.5, 0.1, 1.0, long, long, 3);
+        assert_eq!(edging.duration(), Duration::MAX);
+        assert!((edging.sample(Duration::from_secs(1)) - 0.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn sine_wave_matches_the_true_cosine_envelope() {
+        let mut sine = SineWave::new(0.8, Duration::from_secs(1));
+        for (secs, expected) in [(0.0, 0.0), (0.25, 0.4), (0.5, 0.8), (1.0, 0.0)] {
+            let value = sine.sample(Duration::from_secs_f64(secs));
+            assert!((value - expected).abs() < 1e-9, "{secs}: {value}");
+        }
+        // no phase drift after many cycles
+        let value = sine.sample(Duration::from_secs_f64(500.5));
+        assert!((value - 0.8).abs() < 1e-9, "{value}");
+    }
+
+    #[test]
+    fn pulse_is_high_for_its_duty_cycle() {
+        let phases = [0.0, 0.2, 0.3, 0.6, 0.9];
+        for (duty, expected) in [
+            (0.0, [false, false, false, false, false]),
+            (0.25, [true, true, false, false, false]),
+            (0.5, [true, true, true, false, false]),
+            (1.0, [true, true, true, true, true]),
+        ] {
+            let mut pulse = Pulse::new(0.7, Duration::from_secs(2), duty);
+            let high: Vec<bool> = phases
+                .iter()
+                .map(|phase| pulse.sample(Duration::from_secs_f64(phase * 2.0)) == 0.7)
+                .collect();
+            assert_eq!(high, expected, "duty {duty}");
+            assert_eq!(pulse.duration(), Duration::from_secs(2));
+        }
+    }
+
+    #[test]
+    fn wave_packet_peaks_at_its_center() {
+        let mut packet = WavePacket::new(
+            4.0,
+            Duration::from_secs(2),
+            Duration::from_millis(300),
+            0.9,
+            Duration::from_secs(4),
+        );
+        let samples: Vec<f64> = times(400, 10).map(|time| packet.sample(time)).collect();
+        let (peak, highest) = samples
+            .iter()
+            .enumerate()
+            .fold((0, 0.0), |best, (tick, sample)| {
+                if *sample > best.1 {
+                    (tick, *sample)
+                } else {
+                    best
+                }
+            });
+        assert_eq!(peak, 200);
+        assert!((highest - 0.9).abs() < 1e-9);
+        assert!(samples[..50].iter().all(|sample| *sample < 1e-3));
+        assert!(samples[350..].iter().all(|sample| *sample < 1e-3));
+        assert_eq!(packet.duration(), Duration::from_secs(4));
+    }
+
+    #[test]
+    fn adsr_phase_boundaries_and_midpoints() {
+        let mut envelope = Adsr::new(
+            Duration::from_secs(1),
+            Duration::from_secs(1),
+            0.4,
+            Duration::from_secs(2),
+            Duration::from_secs(1),
+            1.0,
+        );
+        assert_eq!(envelope.duration(), Duration::from_secs(5));
+        for (millis, expected) in [
+            (0, 0.0),
+            (500, 0.5),
+            (1000, 1.0),
+            (1500, 0.7),
+            (2000, 0.4),
+            (3000, 0.4),
+            (4000, 0.4),
+            (4500, 0.2),
+            (5000, 0.0),
+        ] {
+            let value = envelope.sample(Duration::from_millis(millis));
+            assert!((value - expected).abs() < 1e-9, "{millis}ms: {value}");
+        }
+    }
+
+    #[test]
+    fn exp_decay_falls_to_one_over_e_per_time_constant() {
+        let mut decay = ExpDecay::new(0.9, Duration::from_millis(500), Duration::from_secs(3));
+        assert_eq!(decay.sample(Duration::ZERO), 0.9);
+        let after = decay.sample(Duration::from_millis(500));
+        assert!((after / 0.9 - 0.3679).abs() < 1e-4);
+        let samples: Vec<f64> = times(300, 10).map(|time| decay.sample(time)).collect();
+        assert!(samples.windows(2).all(|pair| pair[1] < pair[0]));
+        assert_eq!(decay.duration(), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn descending_staircase_steps_from_ceiling_to_floor() {
+        let mut stairs = Staircase::descending(0.1, 0.7, 4, Duration::from_secs(4));
+        let steps: Vec<f64> = [500, 1500, 2500, 3500]
+            .into_iter()
+            .map(|millis| stairs.sample(Duration::from_millis(millis)))
+            .collect();
+        assert_eq!(steps[0], 0.7);
+        assert!((steps[1] - 0.5).abs() < 1e-9 && (steps[2] - 0.3).abs() < 1e-9);
+        assert_eq!(steps[3], 0.1);
+        assert_eq!(stairs.sample(Duration::from_secs(4)), 0.1);
+
+        let mut rising = Staircase::new(0.1, 0.7, 4, Duration::from_secs(4));
+        assert_eq!(rising.sample(Duration::ZERO), 0.1);
+        assert_eq!(rising.sample(Duration::from_millis(3999)), 0.7);
+    }
+
+    #[test]
+    fn table_sine_matches_sine_wave() {
+        let mut table = TableSine::new(0.9, Duration::from_millis(700));
+        let mut sine = SineWave::new(0.9, Duration::from_millis(700));
+        for time in times(1000, 3) {
+            assert!(
+                (table.sample(time) - sine.sample(time)).abs() < 1e-5,
+                "{time:?}"
+            );
+        }
+        assert_eq!(table.duration(), sine.duration());
+    }
+
+    #[test]
+    fn two_rate_multiplies_a_slow_and_a_fast_wave() {
+        let mut texture = TwoRate::new(
+            0.8,
+            Duration::from_secs(4),
+            0.5,
+            Duration::from_millis(250),
+            Duration::from_secs(8),
+        );
+        let mut slow = SineWave::new(0.8, Duration::from_secs(4));
+        let mut fast = SineWave::new(1.0, Duration::from_millis(250));
+        for time in times(800, 10) {
+            let expected = slow.sample(time) * (0.5 + 0.5 * fast.sample(time));
+            assert!((texture.sample(time) - expected).abs() < 1e-9);
+        }
+        // at the peak of the slow wave, the fast wave still dips it by half
+        assert!((texture.sample(Duration::from_secs(2)) - 0.4).abs() < 1e-9);
+        assert!((texture.sample(Duration::from_millis(2125)) - 0.8).abs() < 1e-2);
+        assert_eq!(texture.duration(), Duration::from_secs(8));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn sine_wave_round_trips_through_json() {
+        let mut sine = SineWave::new(0.7, Duration::from_millis(1500));
+        let json = serde_json::to_string(&sine).unwrap();
+        assert!(json.contains("1.5"), "{json}");
+        let mut loaded: SineWave = serde_json::from_str(&json).unwrap();
+        for time in times(100, 30) {
+            assert_eq!(loaded.sample(time), sine.sample(time));
+        }
+    }
+
+    #[test]
+    fn countdown_pulses_get_closer_together() {
+        let mut countdown = Countdown::new(Duration::from_secs(6), 1.0, 5.0);
+        assert_eq!(countdown.duration(), Duration::from_secs(6));
+        let mut starts = Vec::new();
+        let mut last = 0.0;
+        for tick in 0..6000 {
+            let time = Duration::from_millis(tick);
+            let value = countdown.sample(time);
+            assert!(value == 0.0 || value == 1.0);
+            if value > last || tick == 0 && value == 1.0 {
+                starts.push(time);
+            }
+            last = value;
+        }
+        // 1 to 5 beats per second averages 3 over six seconds
+        assert_eq!(starts.len(), 18);
+        let intervals: Vec<_> = starts.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        assert!(intervals.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert!(intervals[0] > Duration::from_millis(750));
+        assert!(*intervals.last().unwrap() < Duration::from_millis(220));
+    }
+
+    #[test]
+    fn step_sequence_duration_saturates() {
+        let sequence = StepSequence::new(vec![1.0, 0.5, 1.0], Duration::MAX / 2, 0.2);
+        assert_eq!(sequence.duration(), Duration::MAX);
+    }
+
+    #[test]
+    fn groove_delays_only_the_off_beats() {
+        // the start and end of each pulse, in milliseconds, sampled between milliseconds to stay off the edges
+        let pulses = |groove: f64| {
+            let levels = vec![1.0, 0.8, 0.6, 0.4];
+            let mut sequence = StepSequence::new(levels, Duration::from_millis(100), groove);
+            let mut pulses: Vec<(u64, u64, f64)> = Vec::new();
+            let mut last = 0.0;
+            for tick in 0..400 {
+                let value = sequence.sample(Duration::from_micros(tick * 1000 + 500));
+                if value != last {
+                    if last > 0.0 {
+                        pulses.last_mut().unwrap().1 = tick;
+                    }
+                    if value > 0.0 {
+                        pulses.push((tick, tick, value));
+                    }
+                }
+                last = value;
+            }
+            pulses
+        };
+        let straight = [
+            (0, 50, 1.0),
+            (100, 150, 0.8),
+            (200, 250, 0.6),
+            (300, 350, 0.4),
+        ];
+        assert_eq!(pulses(0.0), straight);
+        let swung = [
+            (0, 50, 1.0),
+            (120, 170, 0.8),
+            (200, 250, 0.6),
+            (320, 370, 0.4),
+        ];
+        assert_eq!(pulses(0.2), swung);
+        // clamped so off-beats end as the next step starts
+        assert_eq!(pulses(0.9)[1], (150, 200, 0.8));
+    }
+
+    #[test]
+    fn gate_sequence_follows_its_segments() {
+        let segments = vec![
+            (true, Duration::from_secs(1)),
+            (false, Duration::from_millis(500)),
+            (true, Duration::from_millis(500)),
+        ];
+        let mut gates = GateSequence::new(segments, 0.7);
+        assert_eq!(gates.duration(), Duration::from_secs(2));
+        let timeline: Vec<f64> = times(24, 100).map(|time| gates.sample(time)).collect();
+        let mut expected = vec![0.7; 10];
+        expected.extend([0.0; 5]);
+        expected.extend([0.7; 5]);
+        expected.extend([0.0; 4]);
+        assert_eq!(timeline, expected);
+    }
+}
@@ -1,26 +1,59 @@
+use std::collections::VecDeque;
 use std::f64::consts;
-use std::time::Duration;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::{Duration, Instant};
+use std::{error::Error, fmt};
 
-use crate::shapes::Linear;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::shapes::SineWave;
 use crate::Pattern;
 use crate::PatternGenerator;
+use crate::MAX_SAMPLES;
 
 /// Scales the pattern in the time domain by a given scalar.
+///
+/// Time `t` samples the inner pattern at `t / scalar`, so time 0 always maps to the start of the pattern.
+/// The scalar should be positive, times that can't be scaled are sampled at the end of time.
 #[derive(Clone, Debug, PartialEq)]
 pub struct ScaleTime<P: Pattern> {
     pub pattern: P,
     pub scalar: f64,
 }
 
+impl<P: Pattern> ScaleTime<P> {
+    fn scale(&self, length: Duration) -> Duration {
+        if length == Duration::MAX {
+            return length;
+        }
+        Duration::try_from_secs_f64(length.as_secs_f64() * self.scalar).unwrap_or(Duration::MAX)
+    }
+}
+
 impl<P: Pattern> PatternGenerator for ScaleTime<P> {
     fn sample(&mut self, time: Duration) -> f64 {
-        self.pattern.sample(Duration::from_secs_f64(
-            self.scalar * (1.0 / time.as_secs_f64()),
-        ))
+        let scaled =
+            Duration::try_from_secs_f64(time.as_secs_f64() / self.scalar).unwrap_or(Duration::MAX);
+        self.pattern.sample(scaled)
     }
 
     fn duration(&self) -> Duration {
-        self.pattern.duration()
+        self.scale(self.pattern.duration())
+    }
+
+    fn period(&self) -> Duration {
+        self.scale(self.pattern.period())
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
     }
 }
 
@@ -39,6 +72,43 @@ impl<P: Pattern> PatternGenerator for ScaleIntensity<P> {
     fn duration(&self) -> Duration {
         self.pattern.duration()
     }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+}
+
+/// Offsets the intensity of a pattern by a constant amount.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Offset<P: Pattern> {
+    pub pattern: P,
+    pub offset: f64,
+}
+
+impl<P: Pattern> PatternGenerator for Offset<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        self.pattern.sample(time) + self.offset
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
 }
 
 /// Adds two patterns together.
@@ -56,6 +126,14 @@ impl<P: Pattern, Q: Pattern> PatternGenerator for Sum<P, Q> {
     fn duration(&self) -> Duration {
         self.a.duration().max(self.b.duration())
     }
+
+    fn period(&self) -> Duration {
+        self.a.period().max(self.b.period())
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.a, &self.b]
+    }
 }
 
 /// Subtracts two patterns from each other.
@@ -73,6 +151,14 @@ impl<P: Pattern, Q: Pattern> PatternGenerator for Subtract<P, Q> {
     fn duration(&self) -> Duration {
         self.a.duration().max(self.b.duration())
     }
+
+    fn period(&self) -> Duration {
+        self.a.period().max(self.b.period())
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.a, &self.b]
+    }
 }
 
 /// Averages two patterns together.
@@ -90,6 +176,74 @@ impl<P: Pattern, Q: Pattern> PatternGenerator for Average<P, Q> {
     fn duration(&self) -> Duration {
         self.a.duration().max(self.b.duration())
     }
+
+    fn period(&self) -> Duration {
+        self.a.period().max(self.b.period())
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.a, &self.b]
+    }
+}
+
+/// Takes the lower of two patterns at each point in time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Min<P: Pattern, Q: Pattern> {
+    pub a: P,
+    pub b: Q,
+}
+
+impl<P: Pattern, Q: Pattern> PatternGenerator for Min<P, Q> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        self.a.sample(time).min(self.b.sample(time))
+    }
+
+    fn duration(&self) -> Duration {
+        self.a.duration().max(self.b.duration())
+    }
+
+    fn period(&self) -> Duration {
+        self.a.period().max(self.b.period())
+    }
+
+    fn reset(&mut self) {
+        self.a.reset();
+        self.b.reset();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.a, &self.b]
+    }
+}
+
+/// Takes the higher of two patterns at each point in time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Max<P: Pattern, Q: Pattern> {
+    pub a: P,
+    pub b: Q,
+}
+
+impl<P: Pattern, Q: Pattern> PatternGenerator for Max<P, Q> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        self.a.sample(time).max(self.b.sample(time))
+    }
+
+    fn duration(&self) -> Duration {
+        self.a.duration().max(self.b.duration())
+    }
+
+    fn period(&self) -> Duration {
+        self.a.period().max(self.b.period())
+    }
+
+    fn reset(&mut self) {
+        self.a.reset();
+        self.b.reset();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.a, &self.b]
+    }
 }
 
 /// Clamps the pattern to a given range for a buttplug command.
@@ -108,6 +262,14 @@ impl<P: Pattern> PatternGenerator for Clamp<P> {
     fn duration(&self) -> Duration {
         self.pattern.duration()
     }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
 }
 
 /// Scales the pattern to a valid range for a buttplug command.
@@ -124,9 +286,21 @@ impl<P: Pattern> PatternGenerator for ValidScale<P> {
     fn duration(&self) -> Duration {
         self.pattern.duration()
     }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
 }
 
-/// Shifts the pattern by a given time.
+/// Shifts the pattern forward by a given time, skipping its start.
+///
+/// The duration is shortened by the shift, down to zero when shifting past the end of the pattern,
+/// and infinite patterns stay infinite. Samples past the end of the shifted pattern sample the inner pattern
+/// past its own end. To delay a pattern instead, chain it after a `Pause`.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Shift<P: Pattern> {
     pub pattern: P,
@@ -135,11 +309,27 @@ pub struct Shift<P: Pattern> {
 
 impl<P: Pattern> PatternGenerator for Shift<P> {
     fn sample(&mut self, time: Duration) -> f64 {
-        self.pattern.sample(time + self.time_shift)
+        self.pattern.sample(time.saturating_add(self.time_shift))
     }
 
     fn duration(&self) -> Duration {
-        self.pattern.duration() - self.time_shift
+        let duration = self.pattern.duration();
+        if duration == Duration::MAX {
+            return duration;
+        }
+        duration.saturating_sub(self.time_shift)
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
     }
 }
 
@@ -160,6 +350,14 @@ impl<P: Pattern> PatternGenerator for Repeat<P> {
     fn duration(&self) -> Duration {
         Duration::from_secs_f64(self.count * self.pattern.duration().as_secs_f64())
     }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.duration()
+    }
 }
 
 /// Repeats a pattern forever.
@@ -170,16 +368,31 @@ pub struct Forever<P: Pattern> {
 
 impl<P: Pattern> PatternGenerator for Forever<P> {
     fn sample(&mut self, time: Duration) -> f64 {
-        let time_slice = time.as_secs_f64() % self.pattern.duration().as_secs_f64();
+        let length = self.pattern.duration();
+        // a pattern without length has nothing to loop but its start
+        if length.is_zero() {
+            return self.pattern.sample(Duration::ZERO);
+        }
+        let time_slice = time.as_secs_f64() % length.as_secs_f64();
         self.pattern.sample(Duration::from_secs_f64(time_slice))
     }
 
     fn duration(&self) -> Duration {
         Duration::MAX
     }
+
+    fn period(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
 }
 
 /// Chains two patterns together.
+///
+/// `then` starts from its own beginning once `first` ends, so it is sampled at `time - first.duration()`.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Chain<P: Pattern, Q: Pattern> {
     pub first: P,
@@ -191,16 +404,34 @@ impl<P: Pattern, Q: Pattern> PatternGenerator for Chain<P, Q> {
         if time < self.first.duration() {
             self.first.sample(time)
         } else {
-            self.then.sample(time)
+            self.then.sample(time - self.first.duration())
         }
     }
 
     fn duration(&self) -> Duration {
-        self.first.duration() + self.then.duration()
+        self.first.duration().saturating_add(self.then.duration())
+    }
+
+    fn period(&self) -> Duration {
+        // infinite chains report the loop of whichever pattern never ends
+        if self.first.duration() == Duration::MAX {
+            self.first.period()
+        } else if self.then.duration() == Duration::MAX {
+            self.then.period()
+        } else {
+            self.duration()
+        }
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.first, &self.then]
     }
 }
 
 /// Linear crossfade between two patterns over a given duration.
+///
+/// `then` starts from its own beginning `overlap_duration` before `first` ends, so it is sampled at
+/// `time - (first.duration() - overlap_duration)`, both during the overlap and after it.
 pub struct Crossfade<P: Pattern, Q: Pattern> {
     pub first: P,
     pub then: Q,
@@ -216,25 +447,49 @@ impl<P: Pattern, Q: Pattern> Crossfade<P, Q> {
         }
     }
 
+    /// The time at which `then` starts, `overlap_duration` before the end of `first`.
+    fn then_start(&self) -> Duration {
+        self.first.duration().saturating_sub(self.overlap_duration)
+    }
+
     fn sample_overlap(&mut self, time: Duration) -> f64 {
-        let progress = (time - (self.first.duration() - self.overlap_duration)).as_secs_f64()
-            / self.overlap_duration.as_secs_f64();
-        self.first.sample(time) * (1.0 - progress) + self.then.sample(time) * progress
+        let then_time = time - self.then_start();
+        let progress = then_time.as_secs_f64() / self.overlap_duration.as_secs_f64();
+        self.first.sample(time) * (1.0 - progress) + self.then.sample(then_time) * progress
     }
 }
 
 impl<P: Pattern, Q: Pattern> PatternGenerator for Crossfade<P, Q> {
     fn sample(&mut self, time: Duration) -> f64 {
-        if time < self.first.duration() - self.overlap_duration {
+        if time < self.then_start() {
             self.first.sample(time)
         } else if time < self.first.duration() {
             self.sample_overlap(time)
         } else {
-            self.then.sample(time - self.overlap_duration)
+            self.then.sample(time - self.then_start())
         }
     }
+
     fn duration(&self) -> Duration {
-        self.first.duration() + self.then.duration() - self.overlap_duration
+        match self.first.duration().saturating_add(self.then.duration()) {
+            Duration::MAX => Duration::MAX,
+            total => total.saturating_sub(self.overlap_duration),
+        }
+    }
+
+    fn period(&self) -> Duration {
+        // infinite chains report the loop of whichever pattern never ends
+        if self.first.duration() == Duration::MAX {
+            self.first.period()
+        } else if self.then.duration() == Duration::MAX {
+            self.then.period()
+        } else {
+            self.duration()
+        }
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.first, &self.then]
     }
 }
 
@@ -255,4 +510,2971 @@ impl<P: Pattern, M: Pattern> PatternGenerator for AmplitudeModulator<P, M> {
     fn duration(&self) -> Duration {
         self.pattern.duration()
     }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern, &self.modulator]
+    }
+}
+
+/// Modulates the phase of a pattern by another pattern, sampling the carrier `pattern` ahead of time by
+/// the modulator's value in seconds, or behind it for negative values.
+///
+/// Effectively a frequency modulation combinator, the carrier speeds up while the modulator rises and
+/// slows down while it falls. Times before the start of the carrier are clamped to zero.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrequencyModulator<P: Pattern, M: Pattern> {
+    pub pattern: P,
+    pub modulator: M,
+}
+
+impl<P: Pattern, M: Pattern> PatternGenerator for FrequencyModulator<P, M> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let warped = (time.as_secs_f64() + self.modulator.sample(time)).max(0.0);
+        self.pattern
+            .sample(Duration::try_from_secs_f64(warped).unwrap_or(Duration::MAX))
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+        self.modulator.reset();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern, &self.modulator]
+    }
+}
+
+/// Limits how quickly the velocity of a pattern can change, giving jerk-free, S-shaped motion.
+///
+/// Unlike a slew limit, which caps the slope, this caps the acceleration, so the output
+/// speeds up and slows down smoothly when approaching a new level.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccelLimit<P: Pattern> {
+    pub pattern: P,
+    /// The maximum change in velocity per second, in intensity per second squared.
+    pub max_accel: f64,
+    last_time: Option<Duration>,
+    value: f64,
+    velocity: f64,
+}
+
+impl<P: Pattern> AccelLimit<P> {
+    pub fn new(pattern: P, max_accel: f64) -> Self {
+        Self {
+            pattern,
+            max_accel,
+            last_time: None,
+            value: 0.0,
+            velocity: 0.0,
+        }
+    }
+}
+
+impl<P: Pattern> PatternGenerator for AccelLimit<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let target = self.pattern.sample(time);
+        let Some(last_time) = self.last_time.replace(time) else {
+            self.value = target;
+            return target;
+        };
+        let dt = time.saturating_sub(last_time).as_secs_f64();
+        if dt == 0.0 {
+            return self.value;
+        }
+        let distance = target - self.value;
+        // fastest velocity that can still brake to a stop at the target, given braking happens in steps of `dt`
+        let step_change = self.max_accel * dt / 2.0;
+        let braking_speed = ((2.0 * self.max_accel * distance.abs() + step_change.powi(2)).sqrt()
+            - step_change)
+            .max(0.0);
+        let desired_velocity = distance.signum() * (distance.abs() / dt).min(braking_speed);
+        let max_change = self.max_accel * dt;
+        self.velocity += (desired_velocity - self.velocity).clamp(-max_change, max_change);
+        self.value += self.velocity * dt;
+        self.value
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+        self.last_time = None;
+        self.value = 0.0;
+        self.velocity = 0.0;
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+}
+
+/// Modulates the amplitude of a carrier pattern by a sine wave, with the depth of the modulation
+/// controlled by another pattern.
+///
+/// A depth of 0.0 leaves the carrier unchanged, a depth of 1.0 fully modulates it down to 0.0 at the troughs
+/// of the sine wave. Depth values are clamped to 0.0 to 1.0.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DynamicTremolo<C: Pattern, D: Pattern> {
+    pub carrier: C,
+    pub depth: D,
+    pub lfo: SineWave,
+}
+
+impl<C: Pattern, D: Pattern> PatternGenerator for DynamicTremolo<C, D> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let depth = self.depth.sample(time).clamp(0.0, 1.0);
+        self.carrier.sample(time) * (1.0 - depth * (1.0 - self.lfo.sample(time)))
+    }
+
+    fn duration(&self) -> Duration {
+        self.carrier.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.carrier.period()
+    }
+
+    fn reset(&mut self) {
+        self.carrier.reset();
+        self.depth.reset();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.carrier, &self.depth, &self.lfo]
+    }
+}
+
+/// Fades a finite pattern out to 0.0 over the last `tail_fraction` of its duration.
+///
+/// Patterns that loop forever are left unchanged, as they have no end to fade towards.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AutoTail<P: Pattern> {
+    pub pattern: P,
+    pub tail_fraction: f64,
+}
+
+impl<P: Pattern> PatternGenerator for AutoTail<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let duration = self.pattern.duration();
+        let value = self.pattern.sample(time);
+        if duration == Duration::MAX {
+            return value;
+        }
+        let tail = duration.as_secs_f64() * self.tail_fraction.clamp(0.0, 1.0);
+        let remaining = duration.as_secs_f64() - time.as_secs_f64();
+        if remaining >= tail {
+            value
+        } else {
+            value * (remaining / tail).max(0.0)
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+}
+
+/// Rounds `value` to the nearest of `levels` evenly spaced levels from 0.0 to 1.0.
+fn quantize(value: f64, levels: u32) -> f64 {
+    if levels <= 1 {
+        return 0.0;
+    }
+    let steps = (levels - 1) as f64;
+    (value.clamp(0.0, 1.0) * steps).round() / steps
+}
+
+/// Crushes a pattern for a lo-fi effect, reducing both its resolution in intensity and in time.
+///
+/// Output is quantized to `levels` evenly spaced levels between 0.0 and 1.0,
+/// and each value is held for `hold` before the pattern is sampled again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Crush<P: Pattern> {
+    pub pattern: P,
+    pub levels: u32,
+    pub hold: Duration,
+}
+
+impl<P: Pattern> PatternGenerator for Crush<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let held_time = if self.hold.is_zero() {
+            time
+        } else {
+            let hold = self.hold.as_secs_f64();
+            Duration::from_secs_f64((time.as_secs_f64() / hold).floor() * hold)
+        };
+        quantize(self.pattern.sample(held_time), self.levels)
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+}
+
+/// Quantizes a pattern to `steps` evenly spaced levels between 0.0 and 1.0, for devices that work better
+/// with a few discrete intensities than a continuous sweep.
+///
+/// Values are clamped to the valid range and rounded to the nearest level, with values exactly between
+/// two levels rounding up. A single step pins the output to 0.0.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quantize<P: Pattern> {
+    pub pattern: P,
+    pub steps: u32,
+}
+
+impl<P: Pattern> PatternGenerator for Quantize<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        quantize(self.pattern.sample(time), self.steps)
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+}
+
+/// Plays several patterns in turn, each repeated its given number of times, looping forever.
+///
+/// Patterns with more repeats get more airtime over a cycle. An infinite pattern plays forever once its
+/// turn comes, so the patterns after it never play.
+pub struct RoundRobin {
+    pub patterns: Vec<(Box<dyn PatternGenerator>, u32)>,
+}
+
+impl RoundRobin {
+    pub fn new(patterns: Vec<(Box<dyn PatternGenerator>, u32)>) -> Self {
+        Self { patterns }
+    }
+}
+
+impl PatternGenerator for RoundRobin {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let cycle = self.period().as_secs_f64();
+        if cycle == 0.0 {
+            return 0.0;
+        }
+        let mut offset = time.as_secs_f64() % cycle;
+        for (pattern, repeats) in &mut self.patterns {
+            let length = pattern.duration().as_secs_f64();
+            let slot = length * *repeats as f64;
+            if offset < slot {
+                return pattern.sample(Duration::from_secs_f64(offset % length));
+            }
+            offset -= slot;
+        }
+        0.0
+    }
+
+    fn duration(&self) -> Duration {
+        Duration::MAX
+    }
+
+    fn period(&self) -> Duration {
+        self.patterns
+            .iter()
+            .map(|(pattern, repeats)| {
+                pattern
+                    .duration()
+                    .checked_mul(*repeats)
+                    .unwrap_or(Duration::MAX)
+            })
+            .fold(Duration::ZERO, Duration::saturating_add)
+    }
+
+    fn reset(&mut self) {
+        self.patterns.iter_mut().for_each(|(p, _)| p.reset());
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        self.patterns
+            .iter()
+            .map(|(pattern, _)| pattern.as_ref())
+            .collect()
+    }
+}
+
+/// Smooths a pattern by averaging `samples` evenly spaced samples over the `window` leading up to each time.
+///
+/// More samples give a more accurate average at the cost of sampling the inner pattern more often.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MovingAverage<P: Pattern> {
+    pub pattern: P,
+    pub window: Duration,
+    pub samples: u32,
+}
+
+impl<P: Pattern> PatternGenerator for MovingAverage<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        if self.samples <= 1 || self.window.is_zero() {
+            return self.pattern.sample(time);
+        }
+        let step = self.window / (self.samples - 1);
+        // samples before the start of the pattern are clamped to its start
+        (0..self.samples)
+            .map(|i| {
+                self.pattern
+                    .sample(time.saturating_sub(self.window - step * i))
+            })
+            .sum::<f64>()
+            / self.samples as f64
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+}
+
+/// Imposes the amplitude envelope of one pattern onto the shape of another.
+///
+/// The envelope of each pattern is the peak of its absolute value over the `window` leading up to each time.
+/// The output is the `shape` pattern divided by its own envelope, then scaled by the envelope of
+/// the `amplitude` pattern, so it follows the timing of `shape` at the level of `amplitude`.
+///
+/// Finding the envelopes is done by scanning, each sample takes `samples` samples of both inner patterns,
+/// so keep `samples` low at high tickrates.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CrossSynth<A: Pattern, B: Pattern> {
+    pub amplitude: A,
+    pub shape: B,
+    pub window: Duration,
+    pub samples: u32,
+}
+
+/// Finds the peak absolute value of a pattern over the `window` leading up to `time`.
+fn envelope<P: Pattern>(pattern: &mut P, time: Duration, window: Duration, samples: u32) -> f64 {
+    let samples = samples.max(1);
+    let step = window / samples;
+    (0..samples)
+        .map(|i| pattern.sample(time.saturating_sub(step * i)).abs())
+        .fold(0.0, f64::max)
+}
+
+impl<A: Pattern, B: Pattern> PatternGenerator for CrossSynth<A, B> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let level = envelope(&mut self.amplitude, time, self.window, self.samples);
+        let peak = envelope(&mut self.shape, time, self.window, self.samples);
+        if peak == 0.0 {
+            return 0.0;
+        }
+        self.shape.sample(time) / peak * level
+    }
+
+    fn duration(&self) -> Duration {
+        self.shape.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.shape.period()
+    }
+
+    fn reset(&mut self) {
+        self.amplitude.reset();
+        self.shape.reset();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.amplitude, &self.shape]
+    }
+}
+
+/// Mirrors a pattern's intensity around a given axis.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reflect<P: Pattern> {
+    pub pattern: P,
+    pub axis: f64,
+}
+
+impl<P: Pattern> PatternGenerator for Reflect<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        2.0 * self.axis - self.pattern.sample(time)
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+}
+
+/// Inverts a pattern within the range 0.0 to `max`, so a value `v` becomes `max - v`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Invert<P: Pattern> {
+    pub pattern: P,
+    pub max: f64,
+}
+
+impl<P: Pattern> PatternGenerator for Invert<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        self.max - self.pattern.sample(time)
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+}
+
+/// Adds an accent pattern on top of a main pattern, but only while the main pattern is rising.
+///
+/// The slope is estimated from consecutive samples of the main pattern, and the output is clamped to 0.0 to 1.0.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RisingAccent<P: Pattern, A: Pattern> {
+    pub pattern: P,
+    pub accent: A,
+    previous: Option<f64>,
+}
+
+impl<P: Pattern, A: Pattern> RisingAccent<P, A> {
+    pub fn new(pattern: P, accent: A) -> Self {
+        Self {
+            pattern,
+            accent,
+            previous: None,
+        }
+    }
+}
+
+impl<P: Pattern, A: Pattern> PatternGenerator for RisingAccent<P, A> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let value = self.pattern.sample(time);
+        let rising = self.previous.is_some_and(|previous| value > previous);
+        self.previous = Some(value);
+        if rising {
+            (value + self.accent.sample(time)).clamp(0.0, 1.0)
+        } else {
+            value.clamp(0.0, 1.0)
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+        self.accent.reset();
+        self.previous = None;
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern, &self.accent]
+    }
+}
+
+/// Forces a pattern to rest at 0.0 for `min_off` whenever it has stayed above `threshold` for too long.
+///
+/// On-time is accumulated while the output is above `threshold`, and resets whenever the pattern drops
+/// below it on its own. Once it reaches `max_on`, the output is forced to 0.0 for `min_off`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnforceRest<P: Pattern> {
+    pub pattern: P,
+    pub max_on: Duration,
+    pub min_off: Duration,
+    pub threshold: f64,
+    last_time: Option<Duration>,
+    on_time: Duration,
+    rest_until: Option<Duration>,
+}
+
+impl<P: Pattern> EnforceRest<P> {
+    pub fn new(pattern: P, max_on: Duration, min_off: Duration, threshold: f64) -> Self {
+        Self {
+            pattern,
+            max_on,
+            min_off,
+            threshold,
+            last_time: None,
+            on_time: Duration::ZERO,
+            rest_until: None,
+        }
+    }
+}
+
+impl<P: Pattern> PatternGenerator for EnforceRest<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let value = self.pattern.sample(time);
+        let dt = self
+            .last_time
+            .replace(time)
+            .map_or(Duration::ZERO, |last_time| time.saturating_sub(last_time));
+        if let Some(rest_until) = self.rest_until {
+            if time < rest_until {
+                return 0.0;
+            }
+            self.rest_until = None;
+        }
+        if value > self.threshold {
+            self.on_time += dt;
+            if self.on_time >= self.max_on {
+                self.on_time = Duration::ZERO;
+                self.rest_until = Some(time + self.min_off);
+                return 0.0;
+            }
+        } else {
+            self.on_time = Duration::ZERO;
+        }
+        value
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+        self.last_time = None;
+        self.on_time = Duration::ZERO;
+        self.rest_until = None;
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+}
+
+/// Errors from building a `MarkovSwitch` with an invalid transition matrix.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MarkovError {
+    /// The matrix is not square with one row and column per pattern.
+    WrongSize { patterns: usize, rows: usize },
+    /// A row of the matrix has negative probabilities or doesn't sum to 1.0.
+    InvalidRow { row: usize, sum: f64 },
+}
+
+impl fmt::Display for MarkovError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarkovError::WrongSize { patterns, rows } => write!(
+                f,
+                "transition matrix must be {patterns}x{patterns}, but has {rows} rows or a row of the wrong length"
+            ),
+            MarkovError::InvalidRow { row, sum } => write!(
+                f,
+                "row {row} of the transition matrix must have non-negative probabilities summing to 1.0, but sums to {sum}"
+            ),
+        }
+    }
+}
+
+impl Error for MarkovError {}
+
+/// Switches between patterns at random according to a Markov chain, looping forever.
+///
+/// Every `dwell`, the next pattern is picked using the row of `transitions` for the current pattern,
+/// where `transitions[i][j]` is the probability of moving from pattern `i` to pattern `j`.
+/// Playback starts with the first pattern, and each pattern is restarted when it is switched to.
+pub struct MarkovSwitch {
+    patterns: Vec<Box<dyn PatternGenerator>>,
+    transitions: Vec<Vec<f64>>,
+    dwell: Duration,
+    seed: u64,
+    rng: StdRng,
+    step: u64,
+    current: usize,
+}
+
+impl MarkovSwitch {
+    pub fn new(
+        patterns: Vec<Box<dyn PatternGenerator>>,
+        transitions: Vec<Vec<f64>>,
+        dwell: Duration,
+        seed: u64,
+    ) -> Result<Self, MarkovError> {
+        let size = patterns.len();
+        if transitions.len() != size || transitions.iter().any(|row| row.len() != size) {
+            return Err(MarkovError::WrongSize {
+                patterns: size,
+                rows: transitions.len(),
+            });
+        }
+        for (row, probabilities) in transitions.iter().enumerate() {
+            let sum: f64 = probabilities.iter().sum();
+            if (sum - 1.0).abs() > 1e-6 || probabilities.iter().any(|p| *p < 0.0) {
+                return Err(MarkovError::InvalidRow { row, sum });
+            }
+        }
+        Ok(Self {
+            patterns,
+            transitions,
+            dwell,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            step: 0,
+            current: 0,
+        })
+    }
+
+    /// The index of the pattern currently playing.
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    fn next(&mut self) -> usize {
+        let roll: f64 = self.rng.random();
+        let mut cumulative = 0.0;
+        for (next, probability) in self.transitions[self.current].iter().enumerate() {
+            cumulative += probability;
+            if roll < cumulative {
+                return next;
+            }
+        }
+        // rounding can leave the cumulative sum slightly under 1.0
+        self.transitions[self.current]
+            .iter()
+            .rposition(|p| *p > 0.0)
+            .unwrap_or(self.current)
+    }
+}
+
+impl PatternGenerator for MarkovSwitch {
+    fn sample(&mut self, time: Duration) -> f64 {
+        if self.patterns.is_empty() || self.dwell.is_zero() {
+            return 0.0;
+        }
+        let step = (time.as_secs_f64() / self.dwell.as_secs_f64()) as u64;
+        if step < self.step {
+            self.reset();
+        }
+        while self.step < step {
+            self.current = self.next();
+            self.patterns[self.current].reset();
+            self.step += 1;
+        }
+        let pattern = &mut self.patterns[self.current];
+        let mut offset = time.as_secs_f64() - step as f64 * self.dwell.as_secs_f64();
+        let length = pattern.duration().as_secs_f64();
+        if length > 0.0 {
+            offset %= length;
+        }
+        pattern.sample(Duration::from_secs_f64(offset.max(0.0)))
+    }
+
+    fn duration(&self) -> Duration {
+        Duration::MAX
+    }
+
+    fn period(&self) -> Duration {
+        self.dwell
+    }
+
+    fn reset(&mut self) {
+        self.rng = StdRng::seed_from_u64(self.seed);
+        self.step = 0;
+        self.current = 0;
+        self.patterns.iter_mut().for_each(|p| p.reset());
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        self.patterns
+            .iter()
+            .map(|pattern| pattern.as_ref())
+            .collect()
+    }
+}
+
+/// Plays an intro pattern once, then loops a body pattern forever.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Intro<I: Pattern, B: Pattern> {
+    pub intro: I,
+    pub body: B,
+}
+
+impl<I: Pattern, B: Pattern> Intro<I, B> {
+    /// The length of one loop of the body.
+    pub fn loop_period(&self) -> Duration {
+        self.body.duration()
+    }
+}
+
+impl<I: Pattern, B: Pattern> PatternGenerator for Intro<I, B> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let intro = self.intro.duration();
+        if time < intro {
+            return self.intro.sample(time);
+        }
+        let length = self.body.duration();
+        // a body without length has nothing to loop but its start
+        if length.is_zero() {
+            return self.body.sample(Duration::ZERO);
+        }
+        let time_slice = (time - intro).as_secs_f64() % length.as_secs_f64();
+        self.body.sample(Duration::from_secs_f64(time_slice))
+    }
+
+    fn duration(&self) -> Duration {
+        Duration::MAX
+    }
+
+    fn period(&self) -> Duration {
+        self.loop_period()
+    }
+
+    fn reset(&mut self) {
+        self.intro.reset();
+        self.body.reset();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.intro, &self.body]
+    }
+}
+
+/// Ducks a background pattern under a trigger pattern, attenuating the background in proportion to the trigger's level.
+///
+/// The output is `background * (1 - depth * trigger)`, clamped to 0.0 to 1.0.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Duck<B: Pattern, T: Pattern> {
+    pub background: B,
+    pub trigger: T,
+    pub depth: f64,
+}
+
+impl<B: Pattern, T: Pattern> PatternGenerator for Duck<B, T> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        (self.background.sample(time) * (1.0 - self.depth * self.trigger.sample(time)))
+            .clamp(0.0, 1.0)
+    }
+
+    fn duration(&self) -> Duration {
+        self.background.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.background.period()
+    }
+
+    fn reset(&mut self) {
+        self.background.reset();
+        self.trigger.reset();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.background, &self.trigger]
+    }
+}
+
+/// Snaps output below `threshold` down to 0.0, and optionally output above `1.0 - threshold` up to 1.0.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeadZone<P: Pattern> {
+    pub pattern: P,
+    pub threshold: f64,
+    pub snap_high: bool,
+}
+
+impl<P: Pattern> PatternGenerator for DeadZone<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let value = self.pattern.sample(time);
+        if value < self.threshold {
+            0.0
+        } else if self.snap_high && value > 1.0 - self.threshold {
+            1.0
+        } else {
+            value
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+}
+
+/// Expands the dynamics of a pattern by raising its intensity to the power `gamma`.
+///
+/// With a `gamma` above 1.0, quiet parts get quieter while values near 1.0 stay near 1.0, increasing contrast.
+/// Negative values are treated as 0.0.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expand<P: Pattern> {
+    pub pattern: P,
+    pub gamma: f64,
+}
+
+impl<P: Pattern> PatternGenerator for Expand<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        self.pattern.sample(time).max(0.0).powf(self.gamma)
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+}
+
+/// Randomly replaces the tail of some cycles of a pattern with a fill pattern, for rhythmic interest.
+///
+/// At the start of each cycle of `pattern`, a seeded roll decides with the given `probability` whether the
+/// last `fill_fraction` of that cycle plays `fill` instead. The fill starts from its own beginning.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fill<P: Pattern, F: Pattern> {
+    pub pattern: P,
+    pub fill: F,
+    pub probability: f64,
+    pub fill_fraction: f64,
+    seed: u64,
+    rng: StdRng,
+    cycle: Option<u64>,
+    filled: bool,
+}
+
+impl<P: Pattern, F: Pattern> Fill<P, F> {
+    pub fn new(pattern: P, fill: F, probability: f64, fill_fraction: f64, seed: u64) -> Self {
+        Self {
+            pattern,
+            fill,
+            probability,
+            fill_fraction,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            cycle: None,
+            filled: false,
+        }
+    }
+}
+
+impl<P: Pattern, F: Pattern> PatternGenerator for Fill<P, F> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let period = self.pattern.period().as_secs_f64();
+        if period == 0.0 {
+            return self.pattern.sample(time);
+        }
+        let cycle = (time.as_secs_f64() / period) as u64;
+        if self.cycle.is_some_and(|current| cycle < current) {
+            self.rng = StdRng::seed_from_u64(self.seed);
+            self.cycle = None;
+        }
+        while self.cycle.is_none_or(|current| current < cycle) {
+            self.filled = self.rng.random_bool(self.probability.clamp(0.0, 1.0));
+            self.cycle = Some(self.cycle.map_or(0, |current| current + 1));
+        }
+        let offset = time.as_secs_f64() - cycle as f64 * period;
+        let fill_start = period * (1.0 - self.fill_fraction.clamp(0.0, 1.0));
+        if self.filled && offset >= fill_start {
+            self.fill
+                .sample(Duration::from_secs_f64(offset - fill_start))
+        } else {
+            self.pattern.sample(time)
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+        self.fill.reset();
+        self.rng = StdRng::seed_from_u64(self.seed);
+        self.cycle = None;
+        self.filled = false;
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern, &self.fill]
+    }
+}
+
+/// Easing curves, mapping progress from 0.0 to 1.0 onto a curve that also runs from 0.0 to 1.0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EaseKind {
+    /// No easing, progress is unchanged.
+    Linear,
+    /// Starts and ends slowly, with a zero slope at both ends, `3x^2 - 2x^3`.
+    SmoothStep,
+    /// Starts slowly and speeds up, `x^2`.
+    EaseIn,
+    /// Starts quickly and slows down, `1 - (1 - x)^2`.
+    EaseOut,
+    /// Eases in over the first half and out over the second half.
+    EaseInOut,
+}
+
+impl EaseKind {
+    /// Applies the easing curve to `x`, which is clamped to 0.0 to 1.0.
+    pub fn apply(self, x: f64) -> f64 {
+        let x = x.clamp(0.0, 1.0);
+        match self {
+            EaseKind::Linear => x,
+            EaseKind::SmoothStep => x * x * (3.0 - 2.0 * x),
+            EaseKind::EaseIn => x * x,
+            EaseKind::EaseOut => 1.0 - (1.0 - x) * (1.0 - x),
+            EaseKind::EaseInOut => {
+                if x < 0.5 {
+                    2.0 * x * x
+                } else {
+                    1.0 - (2.0 - 2.0 * x).powi(2) / 2.0
+                }
+            }
+        }
+    }
+}
+
+/// Warps the time axis of each cycle of a pattern through an easing curve, so the pattern speeds up
+/// and slows down within each cycle while still completing every cycle on time.
+///
+/// Cycles are one `period` of the pattern.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EaseTime<P: Pattern> {
+    pub pattern: P,
+    pub kind: EaseKind,
+}
+
+impl<P: Pattern> PatternGenerator for EaseTime<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let period = self.pattern.period().as_secs_f64();
+        if period == 0.0 {
+            return self.pattern.sample(time);
+        }
+        let cycle_start = (time.as_secs_f64() / period).floor() * period;
+        let progress = (time.as_secs_f64() - cycle_start) / period;
+        let eased = cycle_start + self.kind.apply(progress) * period;
+        self.pattern.sample(Duration::from_secs_f64(eased))
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+}
+
+/// Remaps the intensity of a pattern through an easing curve, such as softening the onset of ramps
+/// with `EaseKind::SmoothStep`.
+///
+/// The pattern's values are clamped to 0.0 to 1.0 before easing, so the output also stays in that range.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ease<P: Pattern> {
+    pub pattern: P,
+    pub kind: EaseKind,
+}
+
+impl<P: Pattern> PatternGenerator for Ease<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        self.kind.apply(self.pattern.sample(time))
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+}
+
+/// Layers delayed copies of a pattern on top of each other for a thicker, chorus like feel.
+///
+/// Voice `i` plays the pattern delayed by `i * spread`, and the voices are summed, so the output grows with
+/// the number of voices. Scale the pattern down or clamp the chorus to keep it in range.
+/// The first voice is not delayed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chorus<P: Pattern> {
+    voices: Vec<P>,
+    spread: Duration,
+}
+
+impl<P: Pattern + Clone> Chorus<P> {
+    pub fn new(pattern: P, voices: u32, spread: Duration) -> Self {
+        Chorus {
+            voices: vec![pattern; voices.max(1) as usize],
+            spread,
+        }
+    }
+}
+
+impl<P: Pattern> Chorus<P> {
+    /// Delay of voice `i`, saturating at `Duration::MAX` for spreads too long to represent.
+    fn delay(&self, i: usize) -> Duration {
+        u32::try_from(i)
+            .ok()
+            .and_then(|i| self.spread.checked_mul(i))
+            .unwrap_or(Duration::MAX)
+    }
+}
+
+impl<P: Pattern> PatternGenerator for Chorus<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let delays: Vec<Duration> = (0..self.voices.len()).map(|i| self.delay(i)).collect();
+        self.voices
+            .iter_mut()
+            .zip(delays)
+            .map(|(voice, delay)| voice.sample(time.saturating_sub(delay)))
+            .sum()
+    }
+
+    fn duration(&self) -> Duration {
+        let delay = self.delay(self.voices.len() - 1);
+        self.voices[0].duration().saturating_add(delay)
+    }
+
+    fn period(&self) -> Duration {
+        self.voices[0].period()
+    }
+
+    fn reset(&mut self) {
+        for voice in &mut self.voices {
+            voice.reset();
+        }
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        self.voices
+            .iter()
+            .map(|voice| voice as &dyn PatternGenerator)
+            .collect()
+    }
+}
+
+/// Switches between two fixed levels every time a control pattern crosses `midpoint`.
+///
+/// The output starts at `level_a`, and flips to the other level whenever the control moves from one side
+/// of `midpoint` to the other. Any oscillator can drive it, a sine wave control flips it once per half cycle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ToggleOnCross<C: Pattern> {
+    pub control: C,
+    pub level_a: f64,
+    pub level_b: f64,
+    pub midpoint: f64,
+    above: Option<bool>,
+    on_b: bool,
+}
+
+impl<C: Pattern> ToggleOnCross<C> {
+    pub fn new(control: C, level_a: f64, level_b: f64, midpoint: f64) -> Self {
+        Self {
+            control,
+            level_a,
+            level_b,
+            midpoint,
+            above: None,
+            on_b: false,
+        }
+    }
+}
+
+impl<C: Pattern> PatternGenerator for ToggleOnCross<C> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let above = self.control.sample(time) > self.midpoint;
+        if self
+            .above
+            .replace(above)
+            .is_some_and(|was_above| was_above != above)
+        {
+            self.on_b = !self.on_b;
+        }
+        if self.on_b {
+            self.level_b
+        } else {
+            self.level_a
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        self.control.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.control.period()
+    }
+
+    fn reset(&mut self) {
+        self.control.reset();
+        self.above = None;
+        self.on_b = false;
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.control]
+    }
+}
+
+/// Peeks ahead in a pattern, sampling it `lookahead` later than the current time.
+///
+/// The duration is unchanged, so near the end of the pattern the inner pattern is sampled
+/// up to `lookahead` past its own duration. Only use it with patterns that stay valid slightly past `time`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LookAhead<P: Pattern> {
+    pub pattern: P,
+    pub lookahead: Duration,
+}
+
+impl<P: Pattern> PatternGenerator for LookAhead<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        self.pattern.sample(time.saturating_add(self.lookahead))
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+}
+
+/// Keeps a pattern at or below `ceiling` by turning it down ahead of peaks.
+///
+/// Both the current value and the value `lookahead` later are checked, and when either is above `ceiling`
+/// the current value is scaled down by `ceiling / peak`, so the gain starts dropping before the peak arrives.
+///
+/// The inner pattern is only sampled `lookahead` ahead, once per call, so stateful patterns see each time once
+/// and in order. The current value is the latest of those samples at or before `time`, which lags by up to
+/// one tick when `lookahead` isn't a whole number of ticks. Sampling before the earliest buffered time starts over.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Limiter<P: Pattern> {
+    pub pattern: LookAhead<P>,
+    pub ceiling: f64,
+    upcoming: VecDeque<(Duration, f64)>,
+}
+
+impl<P: Pattern> Limiter<P> {
+    pub fn new(pattern: P, ceiling: f64, lookahead: Duration) -> Self {
+        Self {
+            pattern: LookAhead { pattern, lookahead },
+            ceiling,
+            upcoming: VecDeque::new(),
+        }
+    }
+}
+
+impl<P: Pattern> PatternGenerator for Limiter<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let ahead = time.saturating_add(self.pattern.lookahead);
+        let next = self.upcoming.partition_point(|(at, _)| *at <= time);
+        let value = if next == 0 || self.upcoming.back().is_some_and(|(at, _)| *at > ahead) {
+            self.upcoming.clear();
+            let value = self.pattern.pattern.sample(time);
+            self.upcoming.push_back((time, value));
+            value
+        } else {
+            self.upcoming.drain(..next - 1);
+            self.upcoming[0].1
+        };
+        let future = self.pattern.sample(time);
+        self.upcoming.push_back((ahead, future));
+        let peak = value.max(future);
+        if peak > self.ceiling {
+            value * self.ceiling / peak
+        } else {
+            value
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+        self.upcoming.clear();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+}
+
+/// Plays patterns pulled lazily from an iterator one after another, without collecting them up front.
+///
+/// The next pattern is only taken from the iterator once the current one has ended. Because the total
+/// length isn't known in advance, `duration` is `Duration::MAX` until the iterator runs out, and then the
+/// summed duration of every pattern played. Sequences can only move forward in time: sampling a time before
+/// the current pattern started replays the current pattern from its start, and `reset` only resets the current
+/// pattern, since patterns already taken from the iterator can't be taken again.
+pub struct Sequence {
+    patterns: Box<dyn Iterator<Item = Box<dyn PatternGenerator>>>,
+    current: Option<Box<dyn PatternGenerator>>,
+    start: Duration,
+}
+
+impl Sequence {
+    pub fn new<I>(patterns: I) -> Self
+    where
+        I: IntoIterator<Item = Box<dyn PatternGenerator>>,
+        I::IntoIter: 'static,
+    {
+        let mut patterns = patterns.into_iter();
+        let current = patterns.next();
+        Self {
+            patterns: Box::new(patterns),
+            current,
+            start: Duration::ZERO,
+        }
+    }
+}
+
+/// Creates a `Sequence` playing the patterns of an iterator one after another.
+pub fn sequence_from_iter<I>(patterns: I) -> Sequence
+where
+    I: IntoIterator<Item = Box<dyn PatternGenerator>>,
+    I::IntoIter: 'static,
+{
+    Sequence::new(patterns)
+}
+
+impl PatternGenerator for Sequence {
+    fn sample(&mut self, time: Duration) -> f64 {
+        while let Some(current) = &self.current {
+            let end = self.start.saturating_add(current.duration());
+            if time < end {
+                break;
+            }
+            self.start = end;
+            self.current = self.patterns.next();
+        }
+        match &mut self.current {
+            Some(current) => current.sample(time.saturating_sub(self.start)),
+            None => 0.0,
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        match self.current {
+            Some(_) => Duration::MAX,
+            None => self.start,
+        }
+    }
+
+    fn period(&self) -> Duration {
+        match &self.current {
+            Some(current) => current.period(),
+            None => self.duration(),
+        }
+    }
+
+    fn reset(&mut self) {
+        if let Some(current) = &mut self.current {
+            current.reset();
+        }
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        self.current
+            .iter()
+            .map(|pattern| pattern.as_ref())
+            .collect()
+    }
+}
+
+/// Boosts a pattern while a shared flag is set, such as one toggled by a button during a live session.
+///
+/// While `trigger` is set, the output is multiplied by `boost` and clamped between 0.0 and 1.0.
+/// The boost kicks in immediately, and fades back out linearly over `release` once `trigger` is cleared.
+#[derive(Clone, Debug)]
+pub struct PunchIn<P: Pattern> {
+    pub pattern: P,
+    pub trigger: Arc<AtomicBool>,
+    pub boost: f64,
+    pub release: Duration,
+    last_time: Option<Duration>,
+    gain: f64,
+}
+
+impl<P: Pattern> PunchIn<P> {
+    pub fn new(pattern: P, trigger: Arc<AtomicBool>, boost: f64, release: Duration) -> Self {
+        Self {
+            pattern,
+            trigger,
+            boost,
+            release,
+            last_time: None,
+            gain: 1.0,
+        }
+    }
+}
+
+impl<P: Pattern> PatternGenerator for PunchIn<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let value = self.pattern.sample(time);
+        let dt = self
+            .last_time
+            .replace(time)
+            .map_or(Duration::ZERO, |last_time| time.saturating_sub(last_time));
+        if self.trigger.load(Ordering::Acquire) {
+            self.gain = self.boost;
+        } else if self.release.is_zero() {
+            self.gain = 1.0;
+        } else {
+            // fade back to unity by the full boost range over `release`
+            let step = (self.boost - 1.0).abs() * dt.as_secs_f64() / self.release.as_secs_f64();
+            self.gain += (1.0 - self.gain).clamp(-step, step);
+        }
+        (value * self.gain).clamp(0.0, 1.0)
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+        self.last_time = None;
+        self.gain = 1.0;
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+}
+
+/// Follows the peaks of a pattern, jumping up to meet them instantly and falling slowly between them,
+/// like the needle of a VU meter.
+///
+/// The output falls by at most `decay_rate` per second, and never below the pattern itself.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PeakFollower<P: Pattern> {
+    pub pattern: P,
+    pub decay_rate: f64,
+    last_time: Option<Duration>,
+    level: f64,
+}
+
+impl<P: Pattern> PeakFollower<P> {
+    pub fn new(pattern: P, decay_rate: f64) -> Self {
+        Self {
+            pattern,
+            decay_rate,
+            last_time: None,
+            level: 0.0,
+        }
+    }
+}
+
+impl<P: Pattern> PatternGenerator for PeakFollower<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let value = self.pattern.sample(time);
+        let decayed = match self.last_time.replace(time) {
+            Some(last_time) => {
+                self.level - self.decay_rate * time.saturating_sub(last_time).as_secs_f64()
+            }
+            None => value,
+        };
+        self.level = decayed.max(value);
+        self.level
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+        self.last_time = None;
+        self.level = 0.0;
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+}
+
+/// Plays a session of phases one after another, each a pattern played for a set length of time.
+///
+/// Each pattern is sampled relative to the start of its phase, so patterns shorter than their phase should
+/// be repeated or looped forever to fill it. Give the last phase a length of `Duration::MAX` to keep it
+/// playing until the driver is stopped.
+pub struct Phases {
+    pub phases: Vec<(Duration, Box<dyn PatternGenerator>)>,
+}
+
+impl Phases {
+    pub fn new(phases: Vec<(Duration, Box<dyn PatternGenerator>)>) -> Self {
+        Self { phases }
+    }
+}
+
+impl PatternGenerator for Phases {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let mut start = Duration::ZERO;
+        for (length, pattern) in &mut self.phases {
+            let end = start.saturating_add(*length);
+            if time < end {
+                return pattern.sample(time - start);
+            }
+            start = end;
+        }
+        0.0
+    }
+
+    fn duration(&self) -> Duration {
+        self.phases
+            .iter()
+            .fold(Duration::ZERO, |total, (length, _)| {
+                total.saturating_add(*length)
+            })
+    }
+
+    fn period(&self) -> Duration {
+        // a phase that never ends reports the loop of its pattern
+        match self
+            .phases
+            .iter()
+            .find(|(length, _)| *length == Duration::MAX)
+        {
+            Some((_, pattern)) => pattern.period(),
+            None => self.duration(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.phases.iter_mut().for_each(|(_, p)| p.reset());
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        self.phases
+            .iter()
+            .map(|(_, pattern)| pattern.as_ref())
+            .collect()
+    }
+}
+
+/// Which of the two channels of a `Widen` pair a pattern plays.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    Left,
+    Right,
+}
+
+/// One channel of a pair of patterns with the difference between them exaggerated, for a wider feel
+/// across two devices or actuators.
+///
+/// Both patterns are split into their mid, the average of the two, and their side, half the difference.
+/// The side is scaled by `width` and the channel is rebuilt from them and clamped to the valid range,
+/// so a `width` of 1.0 leaves the patterns unchanged and 0.0 plays the mid on both channels.
+/// Use `widen` to build both channels at once.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Widen<L: Pattern, R: Pattern> {
+    pub left: L,
+    pub right: R,
+    pub width: f64,
+    pub channel: Channel,
+}
+
+/// Builds the left and right channels of a pair of patterns widened by `width`.
+pub fn widen<L: Pattern + Clone, R: Pattern + Clone>(
+    left: L,
+    right: R,
+    width: f64,
+) -> (Widen<L, R>, Widen<L, R>) {
+    (
+        Widen {
+            left: left.clone(),
+            right: right.clone(),
+            width,
+            channel: Channel::Left,
+        },
+        Widen {
+            left,
+            right,
+            width,
+            channel: Channel::Right,
+        },
+    )
+}
+
+impl<L: Pattern, R: Pattern> PatternGenerator for Widen<L, R> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let left = self.left.sample(time);
+        let right = self.right.sample(time);
+        let mid = (left + right) / 2.0;
+        let side = (left - right) / 2.0 * self.width;
+        match self.channel {
+            Channel::Left => mid + side,
+            Channel::Right => mid - side,
+        }
+        .clamp(0.0, 1.0)
+    }
+
+    fn duration(&self) -> Duration {
+        self.left.duration().max(self.right.duration())
+    }
+
+    fn period(&self) -> Duration {
+        self.left.period().max(self.right.period())
+    }
+
+    fn reset(&mut self) {
+        self.left.reset();
+        self.right.reset();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.left, &self.right]
+    }
+}
+
+/// Keeps a pattern in the valid range, replacing NaN and infinite values with 0.0 and clamping the rest
+/// between 0.0 and 1.0.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sanitize<P: Pattern> {
+    pub pattern: P,
+}
+
+impl<P: Pattern> PatternGenerator for Sanitize<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let value = self.pattern.sample(time);
+        if value.is_finite() {
+            value.clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+}
+
+/// Gradually speeds up or slows down a pattern, ramping its playback rate from `start_factor` to `end_factor`
+/// over `length`.
+///
+/// A factor of 2.0 plays the pattern twice as fast. The position in the pattern is accumulated between
+/// samples, so the pattern stays continuous as its rate changes. The pattern lasts `length`, so
+/// loop the inner pattern with `forever` to fill it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Accelerando<P: Pattern> {
+    pub pattern: P,
+    pub start_factor: f64,
+    pub end_factor: f64,
+    pub length: Duration,
+    last_time: Duration,
+    phase: f64,
+}
+
+impl<P: Pattern> Accelerando<P> {
+    pub fn new(pattern: P, start_factor: f64, end_factor: f64, length: Duration) -> Self {
+        Self {
+            pattern,
+            start_factor,
+            end_factor,
+            length,
+            last_time: Duration::ZERO,
+            phase: 0.0,
+        }
+    }
+
+    fn factor(&self, time: Duration) -> f64 {
+        let progress = if self.length.is_zero() {
+            1.0
+        } else {
+            (time.as_secs_f64() / self.length.as_secs_f64()).min(1.0)
+        };
+        self.start_factor + (self.end_factor - self.start_factor) * progress
+    }
+}
+
+impl<P: Pattern> PatternGenerator for Accelerando<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        if time < self.last_time {
+            // start accumulating again when sampled from an earlier time, such as when looped
+            self.last_time = Duration::ZERO;
+            self.phase = 0.0;
+        }
+        // the factor changes linearly, so the trapezoidal rule integrates it exactly
+        let dt = (time - self.last_time).as_secs_f64();
+        self.phase += (self.factor(self.last_time) + self.factor(time)) / 2.0 * dt;
+        self.last_time = time;
+        self.pattern
+            .sample(Duration::try_from_secs_f64(self.phase).unwrap_or(Duration::ZERO))
+    }
+
+    fn duration(&self) -> Duration {
+        self.length
+    }
+
+    fn period(&self) -> Duration {
+        if self.length != Duration::MAX {
+            return self.length;
+        }
+        // a ramp that never ends stays near its starting rate
+        Duration::try_from_secs_f64(self.pattern.period().as_secs_f64() / self.start_factor)
+            .unwrap_or(Duration::MAX)
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+        self.last_time = Duration::ZERO;
+        self.phase = 0.0;
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+}
+
+/// Freezes a pattern while a shared flag is set, such as one held by a button during a live session.
+///
+/// While `hold` is set, the output stays at the value the pattern had when it was set. Once it is cleared,
+/// the pattern picks up where it left off, so the held time is added to its duration.
+#[derive(Clone, Debug)]
+pub struct FreezeOnHold<P: Pattern> {
+    pub pattern: P,
+    pub hold: Arc<AtomicBool>,
+    frozen: Option<(f64, Duration)>,
+    held: Duration,
+}
+
+impl<P: Pattern> FreezeOnHold<P> {
+    pub fn new(pattern: P, hold: Arc<AtomicBool>) -> Self {
+        Self {
+            pattern,
+            hold,
+            frozen: None,
+            held: Duration::ZERO,
+        }
+    }
+}
+
+impl<P: Pattern> PatternGenerator for FreezeOnHold<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        if self.hold.load(Ordering::Acquire) {
+            if let Some((value, _)) = self.frozen {
+                return value;
+            }
+            let value = self.pattern.sample(time.saturating_sub(self.held));
+            self.frozen = Some((value, time));
+            return value;
+        }
+        if let Some((_, since)) = self.frozen.take() {
+            self.held += time.saturating_sub(since);
+        }
+        self.pattern.sample(time.saturating_sub(self.held))
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration().saturating_add(self.held)
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+        self.frozen = None;
+        self.held = Duration::ZERO;
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+}
+
+/// Stutters a pattern like a beat repeat effect, looping the last `slice` of it while a shared flag is set.
+///
+/// Recent samples are kept in a buffer covering `slice`. When `trigger` is set, the buffer is captured and
+/// replayed in a loop, holding each captured sample until the next. Once `trigger` is cleared the live pattern
+/// plays again from the current time, as if it had kept playing underneath the repeat.
+#[derive(Clone, Debug)]
+pub struct BeatRepeat<P: Pattern> {
+    pub pattern: P,
+    pub trigger: Arc<AtomicBool>,
+    pub slice: Duration,
+    recent: VecDeque<(Duration, f64)>,
+    captured: Option<(Vec<(Duration, f64)>, Duration)>,
+}
+
+impl<P: Pattern> BeatRepeat<P> {
+    pub fn new(pattern: P, trigger: Arc<AtomicBool>, slice: Duration) -> Self {
+        Self {
+            pattern,
+            trigger,
+            slice,
+            recent: VecDeque::new(),
+            captured: None,
+        }
+    }
+}
+
+impl<P: Pattern> PatternGenerator for BeatRepeat<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        if !self.trigger.load(Ordering::Acquire) {
+            self.captured = None;
+            let value = self.pattern.sample(time);
+            self.recent.push_back((time, value));
+            while self
+                .recent
+                .front()
+                .is_some_and(|&(sampled, _)| time.saturating_sub(sampled) >= self.slice)
+            {
+                self.recent.pop_front();
+            }
+            return value;
+        }
+        let (slice, triggered_at) = self.captured.get_or_insert_with(|| {
+            if self.recent.is_empty() {
+                let value = self.pattern.sample(time);
+                self.recent.push_back((time, value));
+            }
+            (self.recent.iter().copied().collect(), time)
+        });
+        let (start, first) = slice[0];
+        if slice.len() == 1 {
+            return first;
+        }
+        // the last sample is held for the average spacing of the samples, so the loop keeps its rhythm
+        let span = slice[slice.len() - 1].0.saturating_sub(start).as_nanos();
+        let length = span + span / (slice.len() - 1) as u128;
+        if length == 0 {
+            return first;
+        }
+        let offset = time.saturating_sub(*triggered_at).as_nanos() % length;
+        let index = slice
+            .partition_point(|&(sampled, _)| sampled.saturating_sub(start).as_nanos() <= offset);
+        slice[index.saturating_sub(1)].1
+    }
+
+    fn duration(&self) -> Duration {
+        self.pattern.duration()
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+        self.recent.clear();
+        self.captured = None;
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+}
+
+/// Plays a pattern baked into a table of samples, trading memory for a constant cost per sample,
+/// no matter how deeply nested the pattern is.
+///
+/// The pattern is sampled `samples_per_second` times per second once, over its duration or a single period
+/// if it loops forever, and is played back by linearly interpolating between the samples. Infinite patterns
+/// loop the baked period. Stateful patterns are baked as they play from the start, and aren't resampled on `reset`.
+///
+/// Patterns without a finite period can't be baked, since their table would never end, and neither can tables
+/// of more than `MAX_SAMPLES` samples.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Baked {
+    samples: Vec<f64>,
+    samples_per_second: f64,
+    duration: Duration,
+    period: Duration,
+}
+
+/// Errors from baking a pattern into a `Baked` table.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BakeError {
+    /// The pattern has neither a finite duration nor a finite period to bake.
+    Unbounded,
+    /// The number of samples per second is not a positive, finite number.
+    InvalidRate(f64),
+    /// The table would take more than `MAX_SAMPLES` samples.
+    TooLarge,
+}
+
+impl fmt::Display for BakeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BakeError::Unbounded => write!(
+                f,
+                "pattern must have a finite duration or period to be baked"
+            ),
+            BakeError::InvalidRate(rate) => write!(
+                f,
+                "samples per second must be positive and finite, but is {rate}"
+            ),
+            BakeError::TooLarge => write!(
+                f,
+                "pattern would take more than {MAX_SAMPLES} samples to bake"
+            ),
+        }
+    }
+}
+
+impl Error for BakeError {}
+
+impl Baked {
+    pub fn bake<P: Pattern>(mut pattern: P, samples_per_second: f64) -> Result<Self, BakeError> {
+        if !samples_per_second.is_finite() || samples_per_second <= 0.0 {
+            return Err(BakeError::InvalidRate(samples_per_second));
+        }
+        pattern.reset();
+        let duration = pattern.duration();
+        let period = if duration == Duration::MAX {
+            pattern.period()
+        } else {
+            duration
+        };
+        if period == Duration::MAX {
+            return Err(BakeError::Unbounded);
+        }
+        // one extra sample so the end of the pattern can be interpolated to
+        let count = (period.as_secs_f64() * samples_per_second).ceil() + 1.0;
+        if count > MAX_SAMPLES as f64 {
+            return Err(BakeError::TooLarge);
+        }
+        let count = count as usize;
+        let samples = (0..count)
+            .map(|i| {
+                let time = (i as f64 / samples_per_second).min(period.as_secs_f64());
+                pattern.sample(Duration::from_secs_f64(time))
+            })
+            .collect();
+        Ok(Self {
+            samples,
+            samples_per_second,
+            duration,
+            period,
+        })
+    }
+}
+
+impl PatternGenerator for Baked {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let mut time = time.min(self.duration).as_secs_f64();
+        if self.duration == Duration::MAX && !self.period.is_zero() {
+            time %= self.period.as_secs_f64();
+        }
+        let position = (time * self.samples_per_second).max(0.0);
+        let index = position as usize;
+        match (self.samples.get(index), self.samples.get(index + 1)) {
+            (Some(from), Some(to)) => from + (to - from) * position.fract(),
+            (Some(&last), None) => last,
+            _ => self.samples.last().copied().unwrap_or(0.0),
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+}
+
+/// Keeps a stateful pattern in step with wall time when the time it's sampled at jumps, such as after the
+/// driver stalls.
+///
+/// Each sample, the time passed since the last sample is compared against a clock of its own. If the sampled
+/// time ran ahead of the clock by more than `tolerance`, the jump is absorbed and the pattern carries on from
+/// where it was, as if the time had advanced with the clock. Absorbed jumps shift the rest of the pattern,
+/// and are added to its duration. Sampled time falling behind the clock, such as while a `Driver` is paused,
+/// is followed as is, so the pattern resumes where it was paused.
+///
+/// `tolerance` should be comfortably longer than a tick, and the pattern is only meant to be sampled in real time,
+/// so rendering it, or looping it with `Driver::run_looping`, is seen as a series of jumps.
+#[derive(Clone, Debug)]
+pub struct RealtimeSync<P: Pattern> {
+    pub pattern: P,
+    pub tolerance: Duration,
+    clock: fn() -> Instant,
+    last: Option<(Duration, Instant)>,
+    skew: f64,
+}
+
+impl<P: Pattern> RealtimeSync<P> {
+    pub fn new(pattern: P, tolerance: Duration) -> Self {
+        Self::with_clock(pattern, tolerance, Instant::now)
+    }
+
+    /// Creates a `RealtimeSync` that reads the time from `clock` instead of `Instant::now`,
+    /// such as a clock that follows tokio's paused test time.
+    pub fn with_clock(pattern: P, tolerance: Duration, clock: fn() -> Instant) -> Self {
+        Self {
+            pattern,
+            tolerance,
+            clock,
+            last: None,
+            skew: 0.0,
+        }
+    }
+}
+
+impl<P: Pattern> PatternGenerator for RealtimeSync<P> {
+    fn sample(&mut self, time: Duration) -> f64 {
+        let now = (self.clock)();
+        if let Some((last_time, last_instant)) = self.last.replace((time, now)) {
+            let sampled = time.as_secs_f64() - last_time.as_secs_f64();
+            let elapsed = now.duration_since(last_instant).as_secs_f64();
+            // only jumps ahead are absorbed, time that stood still while the clock ran on was paused
+            if sampled - elapsed > self.tolerance.as_secs_f64() {
+                self.skew += sampled - elapsed;
+            }
+        }
+        let synced = (time.as_secs_f64() - self.skew).max(0.0);
+        self.pattern
+            .sample(Duration::try_from_secs_f64(synced).unwrap_or(Duration::MAX))
+    }
+
+    fn duration(&self) -> Duration {
+        let duration = self.pattern.duration().as_secs_f64() + self.skew;
+        Duration::try_from_secs_f64(duration.max(0.0)).unwrap_or(Duration::MAX)
+    }
+
+    fn period(&self) -> Duration {
+        self.pattern.period()
+    }
+
+    fn reset(&mut self) {
+        self.pattern.reset();
+        self.last = None;
+        self.skew = 0.0;
+    }
+
+    fn subpatterns(&self) -> Vec<&dyn PatternGenerator> {
+        vec![&self.pattern]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::shapes::{Constant, Linear, Pause, Pulse, SawWave, TriangleWave};
+
+    #[test]
+    fn bake_refuses_patterns_without_a_finite_period() {
+        let pattern = Constant::new(0.5, Duration::MAX);
+        assert_eq!(Baked::bake(pattern, 100.0), Err(BakeError::Unbounded));
+    }
+
+    #[test]
+    fn bake_refuses_invalid_rates() {
+        let pattern = Constant::new(0.5, Duration::from_secs(1));
+        assert!(matches!(
+            pattern.bake(0.0),
+            Err(BakeError::InvalidRate(rate)) if rate == 0.0
+        ));
+    }
+
+    #[test]
+    fn bake_refuses_huge_tables() {
+        let pattern = Constant::new(0.5, Duration::from_secs(3600 * 24));
+        assert_eq!(Baked::bake(pattern, 1000.0), Err(BakeError::TooLarge));
+    }
+
+    #[test]
+    fn baked_sine_stays_within_the_interpolation_error() {
+        let wavelength = Duration::from_secs(1);
+        let rate = 100.0;
+        let mut live = SineWave::new(1.0, wavelength);
+        let mut baked = SineWave::new(1.0, wavelength).bake(rate).unwrap();
+        // linear interpolation is off by at most max|f''| * h^2 / 8, with f'' at most 1/2 * (2pi/wavelength)^2
+        let h = 1.0 / rate;
+        let bound = 0.5 * (2.0 * std::f64::consts::PI).powi(2) * h * h / 8.0;
+        for ms in 0..1000 {
+            let time = Duration::from_millis(ms);
+            let error = (baked.sample(time) - live.sample(time)).abs();
+            assert!(error <= bound + 1e-12, "{error} > {bound} at {time:?}");
+        }
+    }
+
+    #[test]
+    fn baked_infinite_pattern_loops_its_period() {
+        let mut baked = SawWave::new(1.0, Duration::from_secs(1))
+            .forever()
+            .bake(100.0)
+            .unwrap();
+        assert_eq!(baked.duration(), Duration::MAX);
+        let first = baked.sample(Duration::from_millis(250));
+        let looped = baked.sample(Duration::from_millis(3250));
+        assert!((first - looped).abs() < 1e-9);
+    }
+
+    #[test]
+    fn chain_starts_second_pattern_at_its_own_zero() {
+        let ramp = Linear::new(0.0, 1.0, Duration::from_secs(2));
+        let mut pattern = Constant::new(0.5, Duration::from_secs(1)).chain(ramp);
+        assert_eq!(pattern.duration(), Duration::from_secs(3));
+        assert_eq!(pattern.sample(Duration::from_millis(500)), 0.5);
+        assert!((pattern.sample(Duration::from_secs(2)) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn chain_with_gap_is_silent_during_the_gap() {
+        let mut pattern = Constant::new(0.5, Duration::from_secs(1)).chain_with_gap(
+            Duration::from_secs(1),
+            Linear::new(0.0, 1.0, Duration::from_secs(2)),
+        );
+        assert_eq!(pattern.duration(), Duration::from_secs(4));
+        assert_eq!(pattern.sample(Duration::from_millis(1500)), 0.0);
+        assert!((pattern.sample(Duration::from_secs(3)) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn crossfade_starts_second_pattern_at_the_overlap() {
+        let ramp = Linear::new(0.0, 1.0, Duration::from_secs(2));
+        let mut pattern =
+            Constant::new(1.0, Duration::from_secs(2)).crossfade(ramp, Duration::from_secs(1));
+        assert_eq!(pattern.duration(), Duration::from_secs(3));
+        // halfway through the overlap, then has played for half a second
+        let mid = pattern.sample(Duration::from_millis(1500));
+        assert!((mid - (0.5 * 1.0 + 0.5 * 0.25)).abs() < 1e-9);
+        // after the overlap, then continues from where the overlap left it
+        assert!((pattern.sample(Duration::from_millis(2500)) - 0.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn chained_durations_saturate_with_infinite_patterns() {
+        let infinite = || Constant::new(0.5, Duration::MAX);
+        let short = || Constant::new(0.5, Duration::from_secs(1));
+        assert_eq!(infinite().chain(short()).duration(), Duration::MAX);
+        assert_eq!(
+            short()
+                .crossfade(infinite(), Duration::from_millis(500))
+                .duration(),
+            Duration::MAX
+        );
+    }
+
+    #[test]
+    fn invert_flips_within_the_range() {
+        let mut pattern = SawWave::new(1.0, Duration::from_secs(1))
+            .forever()
+            .invert(0.8);
+        assert_eq!(pattern.period(), Duration::from_secs(1));
+        assert!((pattern.sample(Duration::ZERO) - 0.8).abs() < 1e-9);
+        assert!((pattern.sample(Duration::from_millis(500)) - 0.3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn chorus_sums_delayed_voices() {
+        let ramp = Linear::new(0.0, 1.0, Duration::from_secs(1));
+        let mut chorus = ramp.chorus(3, Duration::from_millis(100));
+        assert_eq!(chorus.duration(), Duration::from_millis(1200));
+        let time = Duration::from_millis(500);
+        let expected: f64 = [0, 100, 200]
+            .iter()
+            .map(|delay| {
+                let mut voice = ramp;
+                voice.sample(time - Duration::from_millis(*delay))
+            })
+            .sum();
+        assert!((chorus.sample(time) - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn chorus_saturates_huge_spreads() {
+        let chorus = Constant::new(0.5, Duration::from_secs(1)).chorus(3, Duration::MAX);
+        assert_eq!(chorus.duration(), Duration::MAX);
+    }
+
+    #[test]
+    fn beat_repeat_loops_the_captured_slice() {
+        let trigger = Arc::new(AtomicBool::new(false));
+        let ramp = Linear::new(0.0, 1.0, Duration::from_secs(1));
+        let mut pattern = ramp.beat_repeat(trigger.clone(), Duration::from_millis(200));
+        for ms in (0..=500).step_by(100) {
+            pattern.sample(Duration::from_millis(ms));
+        }
+        trigger.store(true, Ordering::Release);
+        let looped: Vec<f64> = (600..=900)
+            .step_by(100)
+            .map(|ms| pattern.sample(Duration::from_millis(ms)))
+            .collect();
+        assert_eq!(looped, vec![0.4, 0.5, 0.4, 0.5]);
+        trigger.store(false, Ordering::Release);
+        assert!((pattern.sample(Duration::from_secs(1)) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn beat_repeat_triggered_from_the_start_holds_the_first_sample() {
+        let trigger = Arc::new(AtomicBool::new(true));
+        let ramp = Linear::new(0.0, 1.0, Duration::from_secs(1));
+        let mut pattern = ramp.beat_repeat(trigger, Duration::from_millis(200));
+        assert_eq!(pattern.sample(Duration::from_millis(300)), 0.3);
+        assert_eq!(pattern.sample(Duration::from_millis(600)), 0.3);
+    }
+
+    #[test]
+    fn intro_plays_once_then_loops_the_body() {
+        let ramp = Linear::new(0.0, 1.0, Duration::from_secs(1));
+        let mut pattern = Constant::new(0.9, Duration::from_secs(2)).intro(ramp);
+        assert_eq!(pattern.sample(Duration::from_secs(1)), 0.9);
+        assert!((pattern.sample(Duration::from_millis(2500)) - 0.5).abs() < 1e-9);
+        assert!((pattern.sample(Duration::from_millis(5250)) - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn zero_length_bodies_hold_their_start() {
+        let body = || Constant::new(0.3, Duration::ZERO);
+        let mut intro = Constant::new(0.9, Duration::from_secs(1)).intro(body());
+        assert_eq!(intro.sample(Duration::from_secs(2)), 0.3);
+        let mut forever = body().forever();
+        assert_eq!(forever.sample(Duration::from_secs(2)), 0.3);
+    }
+
+    #[test]
+    fn accel_limit_gives_an_s_shaped_step() {
+        let step = Constant::new(0.0, Duration::from_secs(1))
+            .chain(Constant::new(1.0, Duration::from_secs(3)));
+        let mut pattern = step.accel_limit(4.0);
+        let values: Vec<f64> = (0..400)
+            .map(|tick| pattern.sample(Duration::from_millis(tick * 10)))
+            .collect();
+        let deltas: Vec<f64> = values[100..].windows(2).map(|w| w[1] - w[0]).collect();
+        // speeds up gradually, peaks around the middle, then slows into the target without overshooting
+        assert!(deltas[0] < deltas[40] / 4.0);
+        assert!(deltas[80] < deltas[40] / 2.0);
+        assert!(values.iter().all(|value| *value <= 1.0 + 1e-9));
+        assert!((values[399] - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn dynamic_tremolo_depth_controls_the_modulation() {
+        let carrier = || Constant::new(0.8, Duration::from_secs(1));
+        let times = || (0..100).map(|tick| Duration::from_millis(tick * 10));
+        let mut none = carrier().dynamic_tremolo(
+            Constant::new(0.0, Duration::MAX),
+            Duration::from_millis(200),
+        );
+        assert!(times().all(|time| none.sample(time) == 0.8));
+        let mut full = carrier().dynamic_tremolo(
+            Constant::new(2.0, Duration::MAX),
+            Duration::from_millis(200),
+        );
+        let values: Vec<f64> = times().map(|time| full.sample(time)).collect();
+        let (low, high) = values.iter().fold((f64::MAX, f64::MIN), |(low, high), v| {
+            (low.min(*v), high.max(*v))
+        });
+        assert!(low < 0.01, "{low}");
+        assert!((high - 0.8).abs() < 0.01, "{high}");
+    }
+
+    #[test]
+    fn auto_tail_fades_the_last_fraction() {
+        let mut pattern = Constant::new(0.8, Duration::from_secs(4)).auto_tail(0.25);
+        assert_eq!(pattern.sample(Duration::from_secs(3)), 0.8);
+        assert!((pattern.sample(Duration::from_millis(3500)) - 0.4).abs() < 1e-9);
+        assert!(pattern.sample(Duration::from_secs(4)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn auto_tail_leaves_infinite_patterns_alone() {
+        let mut pattern = Constant::new(0.8, Duration::MAX).auto_tail(0.25);
+        assert_eq!(pattern.sample(Duration::from_secs(1000)), 0.8);
+    }
+
+    #[test]
+    fn crush_steps_values_and_holds_them() {
+        let ramp = Linear::new(0.0, 1.0, Duration::from_secs(1));
+        let mut pattern = ramp.crush(5, Duration::from_millis(300));
+        let values: Vec<f64> = [0, 100, 299, 300, 650, 900]
+            .iter()
+            .map(|ms| pattern.sample(Duration::from_millis(*ms)))
+            .collect();
+        // held from 0.0 for 300ms, then stepped to the nearest quarter of the held value
+        assert_eq!(values, vec![0.0, 0.0, 0.0, 0.25, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn round_robin_gives_airtime_by_repeats() {
+        let mut pattern = RoundRobin::new(vec![
+            (Box::new(Constant::new(0.2, Duration::from_secs(1))), 2),
+            (Box::new(Constant::new(0.9, Duration::from_secs(1))), 1),
+        ]);
+        assert_eq!(pattern.period(), Duration::from_secs(3));
+        let values: Vec<f64> = (0..300)
+            .map(|tick| pattern.sample(Duration::from_millis(tick * 10)))
+            .collect();
+        assert_eq!(values.iter().filter(|v| **v == 0.2).count(), 200);
+        assert_eq!(values.iter().filter(|v| **v == 0.9).count(), 100);
+        // and the cycle starts over
+        assert_eq!(pattern.sample(Duration::from_millis(3500)), 0.2);
+    }
+
+    #[test]
+    fn round_robin_plays_infinite_patterns_forever() {
+        let mut pattern = RoundRobin::new(vec![
+            (Box::new(Constant::new(0.2, Duration::from_secs(1))), 2),
+            (Box::new(Constant::new(0.9, Duration::MAX)), 3),
+            (Box::new(Constant::new(0.5, Duration::from_secs(1))), 1),
+        ]);
+        assert_eq!(pattern.period(), Duration::MAX);
+        assert_eq!(pattern.sample(Duration::from_millis(1500)), 0.2);
+        assert_eq!(pattern.sample(Duration::from_secs(2)), 0.9);
+        assert_eq!(pattern.sample(Duration::from_secs(1_000_000)), 0.9);
+    }
+
+    #[test]
+    fn moving_average_turns_a_step_into_a_ramp() {
+        let step = Constant::new(0.0, Duration::from_secs(1))
+            .chain(Constant::new(1.0, Duration::from_secs(2)));
+        let mut pattern = step.moving_average(Duration::from_millis(500), 501);
+        for ms in [1000, 1100, 1250, 1400, 1500] {
+            let expected = (ms - 1000) as f64 / 500.0;
+            let value = pattern.sample(Duration::from_millis(ms));
+            assert!((value - expected).abs() < 0.01, "{ms}ms: {value}");
+        }
+        assert_eq!(pattern.sample(Duration::from_millis(900)), 0.0);
+        assert_eq!(pattern.sample(Duration::from_millis(1600)), 1.0);
+    }
+
+    #[test]
+    fn cross_synth_follows_shape_at_amplitude_level() {
+        let mut shape = SineWave::new(1.0, Duration::from_millis(100)).forever();
+        let level = Linear::new(0.2, 0.6, Duration::from_secs(1));
+        let mut pattern = shape
+            .clone()
+            .cross_synth(level, Duration::from_millis(100), 50);
+        for ms in (200..1000).step_by(30) {
+            let time = Duration::from_millis(ms);
+            // envelope of the rising line is its latest value
+            let expected = shape.sample(time) * (0.2 + 0.4 * time.as_secs_f64());
+            assert!((pattern.sample(time) - expected).abs() < 0.01, "{ms}ms");
+        }
+    }
+
+    #[test]
+    fn reflect_mirrors_around_the_axis() {
+        let mut pattern = Constant::new(0.3, Duration::from_secs(1)).reflect(0.5);
+        assert!((pattern.sample(Duration::ZERO) - 0.7).abs() < 1e-9);
+        let mut low = Constant::new(0.3, Duration::from_secs(1)).reflect(0.1);
+        assert!((low.sample(Duration::ZERO) + 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rising_accent_only_plays_on_the_rise() {
+        let mut main = TriangleWave::new(0.6, Duration::from_secs(1));
+        let mut pattern = main.rising_accent(Constant::new(0.2, Duration::MAX));
+        let (mut rises, mut falls) = (0, 0);
+        let mut previous = None;
+        // the first half cycle of a triangle wave is held at its peak, so look at the second cycle
+        for time in (100..200).map(|tick| Duration::from_millis(tick * 10)) {
+            let value = main.sample(time);
+            let out = pattern.sample(time);
+            if previous.is_some_and(|previous| value > previous) {
+                assert!((out - (value + 0.2)).abs() < 1e-9);
+                rises += 1;
+            } else {
+                assert!((out - value).abs() < 1e-9);
+                falls += 1;
+            }
+            previous = Some(value);
+        }
+        assert!(rises > 40 && falls > 40);
+    }
+
+    #[test]
+    fn enforce_rest_forces_periodic_rests() {
+        let mut pattern = Constant::new(1.0, Duration::MAX).enforce_rest(
+            Duration::from_secs(1),
+            Duration::from_millis(500),
+            0.5,
+        );
+        let samples: Vec<f64> = (0..500)
+            .map(|tick| pattern.sample(Duration::from_millis(tick * 10)))
+            .collect();
+        let rests = samples
+            .windows(2)
+            .filter(|pair| pair[0] == 1.0 && pair[1] == 0.0)
+            .count();
+        assert_eq!(rests, 3);
+        // each rest lasts `min_off`
+        assert!(samples[100..150].iter().all(|sample| *sample == 0.0));
+        assert_eq!(samples[150], 1.0);
+        assert!(samples[..100].iter().all(|sample| *sample == 1.0));
+    }
+
+    fn markov(transitions: Vec<Vec<f64>>) -> Result<MarkovSwitch, MarkovError> {
+        MarkovSwitch::new(
+            vec![
+                Box::new(Constant::new(0.2, Duration::MAX)),
+                Box::new(Constant::new(0.8, Duration::MAX)),
+            ],
+            transitions,
+            Duration::from_secs(1),
+            7,
+        )
+    }
+
+    #[test]
+    fn markov_switch_follows_deterministic_matrices() {
+        let mut identity = markov(vec![vec![1.0, 0.0], vec![0.0, 1.0]]).unwrap();
+        let mut swap = markov(vec![vec![0.0, 1.0], vec![1.0, 0.0]]).unwrap();
+        for step in 0..10 {
+            let time = Duration::from_millis(step * 1000 + 500);
+            assert_eq!(identity.sample(time), 0.2);
+            let expected = if step % 2 == 0 { 0.2 } else { 0.8 };
+            assert_eq!(swap.sample(time), expected);
+            assert_eq!(swap.current(), step as usize % 2);
+        }
+    }
+
+    #[test]
+    fn markov_switch_rejects_invalid_matrices() {
+        assert!(matches!(
+            markov(vec![vec![0.5, 0.4], vec![0.0, 1.0]]),
+            Err(MarkovError::InvalidRow { row: 0, .. })
+        ));
+        assert!(matches!(
+            markov(vec![vec![1.0, 0.0]]),
+            Err(MarkovError::WrongSize {
+                patterns: 2,
+                rows: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn breathe_swells_once_per_period() {
+        let mut pattern = Constant::new(1.0, Duration::MAX).breathe(Duration::from_secs(4), 0.5);
+        let samples: Vec<f64> = (0..80)
+            .map(|tick| pattern.sample(Duration::from_millis(tick * 100)))
+            .collect();
+        for tick in 0..40 {
+            assert!((samples[tick] - samples[tick + 40]).abs() < 1e-9);
+        }
+        let lowest = samples.iter().copied().fold(f64::INFINITY, f64::min);
+        let highest = samples.iter().copied().fold(0.0, f64::max);
+        assert!((lowest - 0.5).abs() < 1e-2 && (highest - 1.0).abs() < 1e-2);
+
+        let mut ramp = Linear::new(0.0, 1.0, Duration::from_secs(4));
+        let mut still = ramp.breathe(Duration::from_secs(1), 0.0);
+        for tick in 0..40 {
+            let time = Duration::from_millis(tick * 100);
+            assert_eq!(still.sample(time), ramp.sample(time));
+        }
+    }
+
+    #[test]
+    fn duck_turns_the_background_down_under_the_trigger() {
+        let trigger = Constant::new(0.0, Duration::from_secs(1))
+            .chain(Constant::new(1.0, Duration::from_secs(1)));
+        let mut pattern = Constant::new(0.8, Duration::from_secs(2)).duck(trigger, 0.75);
+        assert_eq!(pattern.sample(Duration::from_millis(500)), 0.8);
+        assert!((pattern.sample(Duration::from_millis(1500)) - 0.2).abs() < 1e-9);
+
+        let mut overdriven =
+            Constant::new(0.8, Duration::MAX).duck(Constant::new(1.0, Duration::MAX), 2.0);
+        assert_eq!(overdriven.sample(Duration::ZERO), 0.0);
+    }
+
+    #[test]
+    fn dead_zone_snaps_the_ends_of_the_range() {
+        let ramp = Linear::new(0.0, 1.0, Duration::from_secs(1));
+        let mut low = ramp.dead_zone(0.1, false);
+        let mut both = ramp.dead_zone(0.1, true);
+        assert_eq!(low.sample(Duration::from_millis(50)), 0.0);
+        assert!((low.sample(Duration::from_millis(150)) - 0.15).abs() < 1e-9);
+        assert!((low.sample(Duration::from_millis(950)) - 0.95).abs() < 1e-9);
+        assert_eq!(both.sample(Duration::from_millis(50)), 0.0);
+        assert!((both.sample(Duration::from_millis(500)) - 0.5).abs() < 1e-9);
+        assert_eq!(both.sample(Duration::from_millis(950)), 1.0);
+    }
+
+    #[test]
+    fn expand_increases_contrast() {
+        let mut pattern = Linear::new(0.0, 1.0, Duration::from_secs(1)).expand(2.0);
+        assert!((pattern.sample(Duration::from_millis(500)) - 0.25).abs() < 1e-9);
+        assert!(pattern.sample(Duration::from_millis(990)) > 0.98);
+        assert_eq!(pattern.sample(Duration::from_secs(1)), 1.0);
+        let mut negative = Constant::new(-0.5, Duration::MAX).expand(2.0);
+        assert_eq!(negative.sample(Duration::ZERO), 0.0);
+    }
+
+    fn filled_cycles(probability: f64) -> Vec<bool> {
+        let main = Constant::new(0.2, Duration::from_secs(1)).forever();
+        let mut pattern = main.fill(Constant::new(0.9, Duration::MAX), probability, 0.25, 3);
+        (0..8)
+            .map(|cycle| {
+                let head = pattern.sample(Duration::from_millis(cycle * 1000 + 500));
+                assert_eq!(head, 0.2);
+                pattern.sample(Duration::from_millis(cycle * 1000 + 800)) == 0.9
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fill_replaces_the_tail_by_probability() {
+        assert!(filled_cycles(1.0).into_iter().all(|filled| filled));
+        assert!(filled_cycles(0.0).into_iter().all(|filled| !filled));
+    }
+
+    #[test]
+    fn ease_time_warps_within_each_cycle() {
+        let ramp = Linear::new(0.0, 1.0, Duration::from_secs(1)).forever();
+        let mut eased = ramp.clone().ease_time(EaseKind::EaseIn);
+        for cycle in 0..3 {
+            let start = Duration::from_secs(cycle);
+            assert!(eased.sample(start).abs() < 1e-9);
+            assert!((eased.sample(start + Duration::from_millis(500)) - 0.25).abs() < 1e-9);
+            assert!(eased.sample(start + Duration::from_millis(999)) > 0.99);
+        }
+        let mut linear = ramp.ease_time(EaseKind::Linear);
+        assert!((linear.sample(Duration::from_millis(1500)) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn toggle_on_cross_flips_once_per_half_cycle() {
+        let mut pattern = SineWave::new(1.0, Duration::from_secs(1)).toggle_on_cross(0.2, 0.9, 0.5);
+        let samples: Vec<f64> = (0..300)
+            .map(|tick| pattern.sample(Duration::from_millis(tick * 10 + 5)))
+            .collect();
+        assert_eq!(samples[0], 0.2);
+        assert!(samples
+            .iter()
+            .all(|sample| *sample == 0.2 || *sample == 0.9));
+        let flips = samples.windows(2).filter(|pair| pair[0] != pair[1]).count();
+        assert_eq!(flips, 6);
+    }
+
+    #[test]
+    fn scale_time_stretches_the_wavelength() {
+        let mut original = SineWave::new(1.0, Duration::from_millis(500));
+        let mut stretched = original.scale_time(2.0);
+        for tick in 0..50 {
+            let time = Duration::from_millis(tick * 20);
+            assert!((stretched.sample(time * 2) - original.sample(time)).abs() < 1e-9);
+        }
+        // a full wavelength of the stretched wave is now 1 second
+        assert!(
+            (stretched.sample(Duration::from_secs(1)) - stretched.sample(Duration::ZERO)).abs()
+                < 1e-9
+        );
+        assert!((stretched.sample(Duration::from_millis(500)) - 1.0).abs() < 1e-9);
+        assert_eq!(
+            stretched.sample(Duration::ZERO),
+            original.sample(Duration::ZERO)
+        );
+        assert_eq!(
+            Linear::new(0.0, 1.0, Duration::from_secs(1))
+                .scale_time(2.0)
+                .duration(),
+            Duration::from_secs(2)
+        );
+    }
+
+    #[test]
+    fn look_ahead_samples_the_future() {
+        let mut ramp = Linear::new(0.0, 1.0, Duration::from_secs(2));
+        let mut ahead = ramp.look_ahead(Duration::from_millis(250));
+        for tick in 0..20 {
+            let time = Duration::from_millis(tick * 50);
+            assert_eq!(
+                ahead.sample(time),
+                ramp.sample(time + Duration::from_millis(250))
+            );
+        }
+        assert_eq!(ahead.duration(), ramp.duration());
+    }
+
+    #[test]
+    fn limiter_turns_down_before_the_peak() {
+        let step = Constant::new(0.4, Duration::from_secs(1))
+            .chain(Constant::new(1.0, Duration::from_secs(1)));
+        let mut limited = step.limit(0.5, Duration::from_millis(200));
+        assert_eq!(limited.sample(Duration::from_millis(500)), 0.4);
+        // the peak is already in sight, so the quiet part is turned down too
+        assert!((limited.sample(Duration::from_millis(900)) - 0.2).abs() < 1e-9);
+        assert!((limited.sample(Duration::from_millis(1500)) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn limiter_samples_stateful_patterns_once_in_order() {
+        // a pattern that only moves forward, like a random walk, and logs every time it is sampled
+        #[derive(Clone, Debug, Default, PartialEq)]
+        struct Logged(Vec<Duration>);
+        impl PatternGenerator for Logged {
+            fn sample(&mut self, time: Duration) -> f64 {
+                self.0.push(time);
+                if time >= Duration::from_secs(1) {
+                    1.0
+                } else {
+                    0.4
+                }
+            }
+            fn duration(&self) -> Duration {
+                Duration::from_secs(2)
+            }
+        }
+        let mut limited = Logged::default().limit(0.5, Duration::from_millis(200));
+        let values: Vec<f64> = (0..20)
+            .map(|tick| limited.sample(Duration::from_millis(tick * 100)))
+            .collect();
+        let times = &limited.pattern.pattern.0;
+        // one sample to start from, then one per tick
+        assert_eq!(times.len(), 21);
+        assert!(times.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(values[5], 0.4);
+        assert!((values[9] - 0.2).abs() < 1e-9);
+        assert!((values[15] - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn sequence_plays_an_iterator_lazily() {
+        let levels = [0.2, 0.5, 0.8];
+        let patterns = levels.into_iter().map(|level| {
+            Box::new(Constant::new(level, Duration::from_secs(1))) as Box<dyn PatternGenerator>
+        });
+        let mut sequence = sequence_from_iter(patterns);
+        assert_eq!(sequence.duration(), Duration::MAX);
+        for (second, level) in levels.into_iter().enumerate() {
+            let time = Duration::from_millis(second as u64 * 1000 + 500);
+            assert_eq!(sequence.sample(time), level);
+        }
+        assert_eq!(sequence.sample(Duration::from_millis(3500)), 0.0);
+        // once the iterator has run out the total length is known
+        assert_eq!(sequence.duration(), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn punch_in_boosts_while_triggered_then_releases() {
+        let trigger = Arc::new(AtomicBool::new(false));
+        let mut pattern = Constant::new(0.4, Duration::MAX).punch_in(
+            trigger.clone(),
+            2.0,
+            Duration::from_secs(1),
+        );
+        assert_eq!(pattern.sample(Duration::ZERO), 0.4);
+        trigger.store(true, Ordering::Release);
+        assert!((pattern.sample(Duration::from_millis(100)) - 0.8).abs() < 1e-9);
+        trigger.store(false, Ordering::Release);
+        // half way through the release the gain is half way back
+        assert!((pattern.sample(Duration::from_millis(600)) - 0.6).abs() < 1e-9);
+        assert!((pattern.sample(Duration::from_millis(1100)) - 0.4).abs() < 1e-9);
+        assert!((pattern.sample(Duration::from_millis(2000)) - 0.4).abs() < 1e-9);
+
+        let mut clamped = Constant::new(0.8, Duration::MAX).punch_in(
+            Arc::new(AtomicBool::new(true)),
+            2.0,
+            Duration::ZERO,
+        );
+        assert_eq!(clamped.sample(Duration::ZERO), 1.0);
+    }
+
+    #[test]
+    fn peak_follower_decays_smoothly_between_impulses() {
+        let impulses = Pulse::new(1.0, Duration::from_secs(1), 0.05).repeat(3.0);
+        let mut follower = impulses.peak_follower(0.5);
+        let samples: Vec<f64> = (0..300)
+            .map(|tick| follower.sample(Duration::from_millis(tick * 10 + 5)))
+            .collect();
+        for cycle in samples.chunks(100) {
+            assert!(cycle[..5].iter().all(|sample| *sample == 1.0));
+            // falls at `decay_rate` per second once the impulse has passed
+            let tail = &cycle[5..];
+            assert!(tail
+                .windows(2)
+                .all(|pair| ((pair[0] - pair[1]) - 0.005).abs() < 1e-9));
+            assert!((tail[tail.len() - 1] - 0.525).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn phases_play_each_pattern_in_its_window() {
+        let mut phases = Phases::new(vec![
+            (
+                Duration::from_secs(5),
+                Box::new(Constant::new(0.2, Duration::MAX)),
+            ),
+            (
+                Duration::from_secs(10),
+                Box::new(Linear::new(0.0, 1.0, Duration::from_secs(10))),
+            ),
+            (Duration::MAX, Box::new(Constant::new(0.9, Duration::MAX))),
+        ]);
+        assert_eq!(phases.sample(Duration::from_secs(4)), 0.2);
+        // sampled relative to the start of the phase
+        assert_eq!(phases.sample(Duration::from_secs(5)), 0.0);
+        assert!((phases.sample(Duration::from_secs(10)) - 0.5).abs() < 1e-9);
+        assert_eq!(phases.sample(Duration::from_secs(15)), 0.9);
+        assert_eq!(phases.sample(Duration::from_secs(100_000)), 0.9);
+        assert_eq!(phases.duration(), Duration::MAX);
+    }
+
+    #[test]
+    fn widen_scales_the_difference_between_channels() {
+        let left = Linear::new(0.2, 0.8, Duration::from_secs(1));
+        let right = Constant::new(0.5, Duration::from_secs(1));
+        let (mut same_left, mut same_right) = widen(left, right, 1.0);
+        let (mut wide_left, mut wide_right) = widen(left, right, 1.5);
+        let (mut inner_left, mut inner_right) = (left, right);
+        for tick in 0..50 {
+            let time = Duration::from_millis(tick * 20);
+            let (l, r) = (inner_left.sample(time), inner_right.sample(time));
+            assert!((same_left.sample(time) - l).abs() < 1e-9);
+            assert!((same_right.sample(time) - r).abs() < 1e-9);
+            let wide = wide_left.sample(time) - wide_right.sample(time);
+            assert!((wide - 1.5 * (l - r)).abs() < 1e-9);
+        }
+        let (mut clamped, _) = widen(
+            Constant::new(1.0, Duration::MAX),
+            Constant::new(0.0, Duration::MAX),
+            3.0,
+        );
+        assert_eq!(clamped.sample(Duration::ZERO), 1.0);
+    }
+
+    #[test]
+    fn accelerando_shortens_cycles_without_jumping() {
+        let mut pattern = Linear::new(0.0, 1.0, Duration::from_secs(1))
+            .forever()
+            .accelerando(1.0, 3.0, Duration::from_secs(4));
+        assert_eq!(pattern.duration(), Duration::from_secs(4));
+        let mut wraps = Vec::new();
+        let mut last = pattern.sample(Duration::ZERO);
+        for tick in 1..4000 {
+            let time = Duration::from_millis(tick);
+            let value = pattern.sample(time);
+            if value < last {
+                wraps.push(time);
+            } else {
+                // at most 3x speed, so each millisecond moves at most 0.003 through a one second ramp
+                assert!(
+                    value - last < 0.0031,
+                    "jumped from {last} to {value} at {time:?}"
+                );
+            }
+            last = value;
+        }
+        // the rate ramps from 1 to 3 over 4 seconds, covering 8 cycles
+        assert_eq!(wraps.len(), 7);
+        let cycles: Vec<_> = wraps.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        assert!(cycles.windows(2).all(|pair| pair[1] < pair[0]));
+        assert!(wraps[0] > Duration::from_millis(800));
+        assert!(*cycles.last().unwrap() < Duration::from_millis(400));
+    }
+
+    #[test]
+    fn freeze_on_hold_holds_then_resumes_where_it_left_off() {
+        let hold = Arc::new(AtomicBool::new(false));
+        let mut pattern =
+            Linear::new(0.0, 1.0, Duration::from_secs(2)).freeze_on_hold(hold.clone());
+        assert_eq!(pattern.sample(Duration::from_millis(250)), 0.125);
+        hold.store(true, Ordering::Release);
+        for tick in 0..=10 {
+            let time = Duration::from_millis(500 + tick * 50);
+            assert_eq!(pattern.sample(time), 0.25);
+        }
+        hold.store(false, Ordering::Release);
+        assert_eq!(pattern.sample(Duration::from_millis(1000)), 0.25);
+        assert_eq!(pattern.sample(Duration::from_millis(1500)), 0.5);
+        assert_eq!(pattern.duration(), Duration::from_millis(2500));
+        pattern.reset();
+        assert_eq!(pattern.duration(), Duration::from_secs(2));
+        assert_eq!(pattern.sample(Duration::from_millis(1500)), 0.75);
+    }
+
+    #[test]
+    fn quantize_rounds_to_even_steps() {
+        let quantized = |value: f64, steps: u32| {
+            Constant::new(value, Duration::from_secs(1))
+                .quantize(steps)
+                .sample(Duration::ZERO)
+        };
+        assert_eq!(quantized(0.49, 2), 0.0);
+        assert_eq!(quantized(0.5, 2), 1.0);
+        assert_eq!(quantized(0.2, 4), 1.0 / 3.0);
+        assert_eq!(quantized(0.6, 4), 2.0 / 3.0);
+        assert_eq!(quantized(0.9, 4), 1.0);
+        // exactly between two levels rounds up
+        assert_eq!(quantized(0.25, 3), 0.5);
+        assert_eq!(quantized(0.75, 3), 1.0);
+        assert_eq!(quantized(0.7, 1), 0.0);
+        assert_eq!(quantized(1.4, 4), 1.0);
+        assert_eq!(quantized(-0.3, 4), 0.0);
+
+        let mut stepped = Linear::new(0.0, 1.0, Duration::from_secs(1)).quantize(5);
+        for tick in 0..=100 {
+            let value = stepped.sample(Duration::from_millis(tick * 10));
+            assert!([0.0, 0.25, 0.5, 0.75, 1.0].contains(&value));
+        }
+    }
+
+    #[test]
+    fn smooth_step_is_flat_at_both_ends() {
+        let ease = |kind: EaseKind, value: f64| {
+            Constant::new(value, Duration::from_secs(1))
+                .ease(kind)
+                .sample(Duration::ZERO)
+        };
+        assert_eq!(ease(EaseKind::SmoothStep, 0.0), 0.0);
+        assert_eq!(ease(EaseKind::SmoothStep, 0.5), 0.5);
+        assert_eq!(ease(EaseKind::SmoothStep, 1.0), 1.0);
+        // finite differences at the ends are second order in the step, unlike a linear ramp
+        let h = 1e-4;
+        assert!(ease(EaseKind::SmoothStep, h) / h < 1e-3);
+        assert!((1.0 - ease(EaseKind::SmoothStep, 1.0 - h)) / h < 1e-3);
+        assert!((ease(EaseKind::Linear, h) / h - 1.0).abs() < 1e-9);
+
+        assert_eq!(ease(EaseKind::EaseIn, 0.5), 0.25);
+        assert_eq!(ease(EaseKind::EaseOut, 0.5), 0.75);
+        assert_eq!(ease(EaseKind::EaseInOut, 0.25), 0.125);
+        assert_eq!(ease(EaseKind::EaseInOut, 0.75), 0.875);
+        assert_eq!(ease(EaseKind::EaseIn, 1.5), 1.0);
+        assert_eq!(ease(EaseKind::EaseOut, -0.5), 0.0);
+    }
+
+    #[test]
+    fn min_and_max_clip_and_floor_the_other_pattern() {
+        let level = Constant::new(0.5, Duration::from_secs(3));
+        let sine = SineWave::new(1.0, Duration::from_secs(1));
+        let mut reference = sine;
+        let mut clipped = sine.min_with(level);
+        let mut floored = sine.max_with(level);
+        assert_eq!(clipped.duration(), Duration::from_secs(3));
+        assert_eq!(floored.duration(), Duration::from_secs(3));
+        let (mut clips, mut floors) = (0, 0);
+        for tick in 0..100 {
+            let time = Duration::from_millis(tick * 10);
+            let wave = reference.sample(time);
+            let (low, high) = (clipped.sample(time), floored.sample(time));
+            assert!(low <= 0.5 && high >= 0.5);
+            assert_eq!(low, wave.min(0.5));
+            assert_eq!(high, wave.max(0.5));
+            clips += usize::from(wave > 0.5);
+            floors += usize::from(wave < 0.5);
+        }
+        assert!(clips > 30 && floors > 30);
+    }
+
+    #[test]
+    fn frequency_modulation_warps_the_carrier() {
+        let carrier = SineWave::new(1.0, Duration::from_millis(200));
+        let mut plain = carrier;
+        let mut modulated =
+            carrier.modulate_frequency(SineWave::new(0.05, Duration::from_secs(2)).forever());
+        assert_eq!(modulated.duration(), carrier.duration());
+        let mut largest_difference: f64 = 0.0;
+        for tick in 0..200 {
+            let time = Duration::from_millis(tick * 10);
+            let value = modulated.sample(time);
+            assert!(value.is_finite());
+            assert!((0.0..=1.0).contains(&value));
+            largest_difference = largest_difference.max((value - plain.sample(time)).abs());
+        }
+        assert!(largest_difference > 0.5, "{largest_difference}");
+        // a constant modulator plays the carrier ahead by its value in seconds
+        let modulator = Constant::new(0.05, Duration::from_secs(1));
+        let mut shifted = carrier.modulate_frequency(modulator);
+        assert_eq!(
+            shifted.sample(Duration::ZERO),
+            plain.sample(Duration::from_millis(50))
+        );
+    }
+
+    /// Tokio's clock, which only moves when advanced in tests started paused.
+    fn tokio_clock() -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn realtime_sync_absorbs_jumps_in_time() {
+        let ramp = Linear::new(0.0, 1.0, Duration::from_secs(10));
+        let mut synced = RealtimeSync::with_clock(ramp, Duration::from_millis(200), tokio_clock);
+        assert_eq!(synced.sample(Duration::ZERO), 0.0);
+        // a stall makes the driver's time jump five seconds ahead at once
+        assert_eq!(synced.sample(Duration::from_secs(5)), 0.0);
+        assert_eq!(synced.duration(), Duration::from_secs(15));
+        // afterwards the pattern carries on in step with the clock
+        tokio::time::advance(Duration::from_millis(50)).await;
+        let value = synced.sample(Duration::from_millis(5050));
+        assert!((value - 0.005).abs() < 1e-9, "{value}");
+        // time standing still while the clock runs on is a pause, and carries on from where it was
+        tokio::time::advance(Duration::from_secs(3)).await;
+        let value = synced.sample(Duration::from_millis(5070));
+        assert!((value - 0.007).abs() < 1e-9, "{value}");
+        assert_eq!(synced.duration(), Duration::from_secs(15));
+        synced.reset();
+        assert_eq!(synced.duration(), Duration::from_secs(10));
+        let mut unsynced = ramp;
+        assert_eq!(unsynced.sample(Duration::from_secs(5)), 0.5);
+    }
+
+    #[test]
+    fn shift_shortens_the_duration_but_never_below_zero() {
+        let ramp = Linear::new(0.0, 1.0, Duration::from_secs(2));
+        let mut shifted = ramp.shift(Duration::from_millis(500));
+        assert_eq!(shifted.duration(), Duration::from_millis(1500));
+        assert_eq!(shifted.sample(Duration::ZERO), 0.25);
+        assert_eq!(
+            ramp.shift(Duration::ZERO).duration(),
+            Duration::from_secs(2)
+        );
+        let past_the_end = ramp.shift(Duration::from_secs(3));
+        assert_eq!(past_the_end.duration(), Duration::ZERO);
+        let endless = SineWave::new(1.0, Duration::from_secs(1)).forever();
+        assert_eq!(
+            endless.shift(Duration::from_secs(3)).duration(),
+            Duration::MAX
+        );
+        // delaying is done by chaining after a pause, which lengthens the pattern instead
+        let mut delayed = Pause::new(Duration::from_millis(500)).chain(ramp);
+        assert_eq!(delayed.duration(), Duration::from_millis(2500));
+        assert_eq!(delayed.sample(Duration::from_millis(1500)), 0.5);
+        // a pattern shifted past its end is skipped in a chain
+        let mut chained = past_the_end.chain(Constant::new(0.3, Duration::from_secs(1)));
+        assert_eq!(chained.duration(), Duration::from_secs(1));
+        assert_eq!(chained.sample(Duration::ZERO), 0.3);
+    }
 }